@@ -1,47 +1,146 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender},
+    time::Duration,
+};
+
 use color_eyre::Result;
+use notify::RecursiveMode;
 use ratatui::{
     buffer::Buffer,
     crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
-    layout::{Constraint, Layout, Rect},
+    layout::{self, Constraint, Layout, Rect},
     style::{Color, Style},
     symbols,
     text::Line,
-    widgets::{Block, Borders, List, ListItem, ListState, Padding, StatefulWidget, Widget},
+    widgets::{Block, Borders, Widget},
     DefaultTerminal,
 };
 use tui_input::backend::crossterm::EventHandler;
 
-use crate::{file::TableFile, views::*};
+use crate::{
+    file::{Loaded, TableFile},
+    views::*,
+    watch::Watcher,
+};
+
+/// How long to wait for input before redrawing,
+/// so that progress made in the background is shown.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Shown under the filter prompt.
+const FILTER_EXAMPLE: &str =
+    "e.g. status == \"error\" && latency > 250, host ~ /^db-/i, !(`first name` != \"Ada\")";
+
+/// Decides whether a file created in a watched directory is added,
+/// given the directory and the file's path, and if so opens it.
+type NewFile = Box<dyn Fn(&Path, &Path) -> Option<TableFile>>;
 
 pub struct App {
     state: AppState,
     finder: FinderView,
     finding: bool,
+    diagnostics: DiagnosticsView,
+    diagnosing: bool,
+    columns: ColumnsView,
+    inspecting: bool,
+    query: PromptView,
+    querying: bool,
+    filter: PromptView,
+    filtering: bool,
     file_views: Vec<FileView>,
-    list_state: ListState,
+    tree: FileTreeView,
+    browsing: bool,
     selected_file: usize,
     maximized: bool,
+    tx: Sender<Loaded>,
+    rx: Receiver<Loaded>,
+
+    // Directories that new files are added from,
+    // as given and as canonical paths.
+    watcher: Option<Watcher>,
+    watched_dirs: Vec<(PathBuf, PathBuf)>,
+    new_file: NewFile,
 }
 impl App {
     pub fn new(files: Vec<TableFile>) -> Self {
-        if files.is_empty() {
-            panic!("No files found.");
-        }
-
-        let file_views = files.into_iter().map(|file| FileView::new(file)).collect();
+        let file_views: Vec<_> = files.into_iter().map(FileView::new).collect();
+        let tree = FileTreeView::new(file_views.iter().map(FileView::path));
+        let (tx, rx) = mpsc::channel();
         let mut app = App {
+            tx,
+            rx,
             file_views,
             selected_file: 0,
             finding: false,
+            diagnosing: false,
+            inspecting: false,
+            querying: false,
+            filtering: false,
+            browsing: false,
             maximized: false,
-            list_state: ListState::default(),
+            tree,
             finder: FinderView::default(),
+            diagnostics: DiagnosticsView::default(),
+            columns: ColumnsView::default(),
+            query: PromptView::new("SQL query", None, "enter:run esc:cancel"),
+            filter: PromptView::new(
+                "Filter",
+                Some(FILTER_EXAMPLE),
+                "enter:apply (empty to clear) esc:cancel",
+            ),
             state: AppState::default(),
+            watcher: None,
+            watched_dirs: vec![],
+            new_file: Box::new(|_, _| None),
         };
         app.try_load_file();
 
         app
     }
+
+    /// Reload files when they change on disk, and add files
+    /// created in `dirs` if `new_file` opens them.
+    pub fn watch(
+        mut self,
+        dirs: Vec<PathBuf>,
+        new_file: impl Fn(&Path, &Path) -> Option<TableFile> + 'static,
+    ) -> Self {
+        let dirs: Vec<_> = dirs
+            .into_iter()
+            .filter_map(|dir| {
+                let canonical = dir.canonicalize().ok()?;
+                Some((dir, canonical))
+            })
+            .collect();
+
+        // Other files are watched through the directories they're in.
+        let parents: BTreeSet<_> = self
+            .file_views
+            .iter()
+            .filter_map(|view| {
+                let path = view.path()?.canonicalize().ok()?;
+                path.parent().map(Path::to_path_buf)
+            })
+            .filter(|parent| !dirs.iter().any(|(_, dir)| parent.starts_with(dir)))
+            .collect();
+        let watched = dirs
+            .iter()
+            .map(|(_, dir)| (dir.as_path(), RecursiveMode::Recursive))
+            .chain(
+                parents
+                    .iter()
+                    .map(|dir| (dir.as_path(), RecursiveMode::NonRecursive)),
+            );
+
+        // Live reloading is a nicety, so the viewer
+        // still works if the files can't be watched.
+        self.watcher = Watcher::new(watched).ok();
+        self.watched_dirs = dirs;
+        self.new_file = Box::new(new_file);
+        self
+    }
 }
 
 #[derive(Default, Clone, Copy, PartialEq, Eq)]
@@ -56,11 +155,100 @@ impl App {
         while self.state == AppState::Running {
             terminal.draw(|frame| frame.render_widget(&mut self, frame.area()))?;
             self.handle_events()?;
+            self.receive_loaded();
+            self.receive_scans();
+            self.receive_changes();
         }
         Ok(())
     }
 
+    /// Hand off any files that finished loading in the background.
+    fn receive_loaded(&mut self) {
+        while let Ok(Loaded {
+            file_idx,
+            progress,
+            info,
+            result,
+            query,
+        }) = self.rx.try_recv()
+        {
+            let view = &mut self.file_views[file_idx];
+            if !query {
+                view.finish_loading(&progress, info, result);
+                continue;
+            }
+
+            // A query that fails to run is brought back up with
+            // the error, so that it can be fixed and run again.
+            match view.finish_query(&progress, result) {
+                Ok(()) if !self.querying => self.query.reset(),
+                Ok(()) => {}
+                Err(err) => {
+                    self.query.set_error(err.to_string());
+                    self.querying = file_idx == self.selected_file;
+                }
+            }
+        }
+    }
+
+    /// Show the rows of any sheets that finished being
+    /// sorted, filtered or searched in the background.
+    fn receive_scans(&mut self) {
+        for view in &mut self.file_views {
+            view.poll_scans();
+        }
+    }
+
+    /// Reload files that changed on disk and add any new ones.
+    fn receive_changes(&mut self) {
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+        for path in watcher.changed() {
+            self.file_changed(&path);
+        }
+    }
+
+    /// Reload the file at the canonical `path`, or add it if it's new.
+    fn file_changed(&mut self, path: &Path) {
+        let file_idx = self.file_views.iter().position(|view| {
+            view.path()
+                .and_then(|path| path.canonicalize().ok())
+                .is_some_and(|canonical| canonical == path)
+        });
+        match file_idx {
+            Some(file_idx) => self.file_views[file_idx].refresh(file_idx, &self.tx),
+            None => self.add_file(path),
+        }
+    }
+
+    /// Add a file created in one of the watched directories.
+    fn add_file(&mut self, path: &Path) {
+        if !path.is_file() || !TableFile::is_discoverable(path) {
+            return;
+        }
+        let Some((dir, relative)) = self
+            .watched_dirs
+            .iter()
+            .find_map(|(dir, canonical)| Some((dir, path.strip_prefix(canonical).ok()?)))
+        else {
+            return;
+        };
+        let path = dir.join(relative);
+        if let Some(file) = (self.new_file)(dir, &path) {
+            let file_idx = self.file_views.len();
+            self.tree.add(file_idx, Some(&path));
+            self.file_views.push(FileView::new(file));
+            if file_idx == 0 {
+                self.try_load_file();
+            }
+        }
+    }
+
     fn handle_events(&mut self) -> std::io::Result<()> {
+        if !event::poll(POLL_INTERVAL)? {
+            return Ok(());
+        }
         if let Event::Key(key) = event::read()? {
             if key.kind == KeyEventKind::Press {
                 if self.finding {
@@ -69,6 +257,7 @@ impl App {
                             if let Some((file_id, sheet_id)) = self.finder.get_selected() {
                                 self.selected_file = file_id;
                                 self.file_views[file_id].select_sheet(sheet_id);
+                                self.tree.reveal(file_id);
                                 self.try_load_file();
                                 self.finding = false;
                             }
@@ -90,22 +279,123 @@ impl App {
                                     .file_views
                                     .iter()
                                     .enumerate()
-                                    .map(|(i, file)| {
+                                    .flat_map(|(i, file)| {
                                         file.search_options()
                                             .into_iter()
                                             .enumerate()
                                             .map(move |(j, name)| ((i, j), name))
                                     })
-                                    .flatten()
                                     .collect();
                                 self.finder.query.handle_event(&Event::Key(key));
                                 self.finder.update_results(&opts);
                             }
                         }
                     }
-                } else {
+                } else if self.diagnosing {
+                    match key.code {
+                        KeyCode::Enter => {
+                            if let Some(row) = self.diagnostics.get_selected() {
+                                self.file_views[self.selected_file].select_row(row);
+                                self.diagnosing = false;
+                            }
+                        }
+                        KeyCode::Char('j') | KeyCode::Down => self.diagnostics.select_next(),
+                        KeyCode::Char('k') | KeyCode::Up => self.diagnostics.select_previous(),
+                        KeyCode::Char('e') | KeyCode::Esc => self.diagnosing = false,
+                        _ => {}
+                    }
+                } else if self.inspecting {
+                    match key.code {
+                        KeyCode::Enter => {
+                            if let Some(col) = self.columns.get_selected() {
+                                self.file_views[self.selected_file].select_column(col);
+                                self.inspecting = false;
+                            }
+                        }
+                        KeyCode::Char('j') | KeyCode::Down => self.columns.select_next(),
+                        KeyCode::Char('k') | KeyCode::Up => self.columns.select_previous(),
+                        KeyCode::Char('i') | KeyCode::Esc => self.inspecting = false,
+                        _ => {}
+                    }
+                } else if self.querying {
+                    match key.code {
+                        KeyCode::Enter => {
+                            let view = &mut self.file_views[self.selected_file];
+                            match view.run_query(
+                                self.query.input.value(),
+                                self.selected_file,
+                                &self.tx,
+                            ) {
+                                // The query is kept until it's run, in case it fails.
+                                Ok(()) => {
+                                    self.query.clear_error();
+                                    self.querying = false;
+                                }
+                                Err(err) => self.query.set_error(err.to_string()),
+                            }
+                        }
+                        KeyCode::Esc => self.querying = false,
+                        _ => {
+                            self.query.input.handle_event(&Event::Key(key));
+                        }
+                    }
+                } else if self.filtering {
+                    match key.code {
+                        KeyCode::Enter => {
+                            let view = &mut self.file_views[self.selected_file];
+                            match view.filter(self.filter.input.value()) {
+                                Ok(()) => {
+                                    self.filter.clear_error();
+                                    self.filtering = false;
+                                }
+                                Err(err) => self.filter.set_error(err.to_string()),
+                            }
+                        }
+                        KeyCode::Esc => self.filtering = false,
+                        _ => {
+                            self.filter.input.handle_event(&Event::Key(key));
+                        }
+                    }
+                } else if self
+                    .file_views
+                    .get(self.selected_file)
+                    .is_some_and(FileView::is_searching)
+                {
                     let view = &mut self.file_views[self.selected_file];
                     match key.code {
+                        KeyCode::Enter => view.finish_search(),
+                        KeyCode::Esc => view.cancel_search(),
+                        KeyCode::Tab => view.cycle_search_mode(),
+                        _ => view.edit_search(&Event::Key(key)),
+                    }
+                } else if self.browsing {
+                    match key.code {
+                        KeyCode::Char('J') => self.next_file(),
+                        KeyCode::Char('K') => self.previous_file(),
+                        KeyCode::Char('j') | KeyCode::Down => self.tree.select_next(),
+                        KeyCode::Char('k') | KeyCode::Up => self.tree.select_previous(),
+                        KeyCode::Char('h') | KeyCode::Left => self.tree.collapse(),
+                        KeyCode::Char('l') | KeyCode::Right => match self.tree.get_selected() {
+                            Some(file_idx) => self.open_file(file_idx),
+                            None => self.tree.expand(),
+                        },
+                        KeyCode::Enter | KeyCode::Char(' ') => match self.tree.get_selected() {
+                            Some(file_idx) => {
+                                self.open_file(file_idx);
+                                self.browsing = false;
+                            }
+                            None => self.tree.toggle(),
+                        },
+                        KeyCode::Tab | KeyCode::Esc => self.browsing = false,
+                        _ => {}
+                    }
+                } else if let Some(view) = self.file_views.get_mut(self.selected_file) {
+                    match key.code {
+                        KeyCode::Tab => {
+                            self.tree.reveal(self.selected_file);
+                            self.browsing = true;
+                            self.maximized = false;
+                        }
                         KeyCode::Char('J') => self.next_file(),
                         KeyCode::Char('K') => self.previous_file(),
                         KeyCode::Char('j') | KeyCode::Down => {
@@ -124,38 +414,69 @@ impl App {
                         }
                         KeyCode::Char('l') | KeyCode::Right => view.next_column(),
                         KeyCode::Char('h') | KeyCode::Left => view.previous_column(),
+                        KeyCode::Char('p') => view.toggle_pin(),
+                        KeyCode::Char('P') => view.pin_leading(),
+                        KeyCode::Char('o') => view.sort(false),
+                        KeyCode::Char('O') => view.sort(true),
+                        KeyCode::Char('f') => self.filtering = true,
+                        KeyCode::Char('/') => view.start_search(),
+                        KeyCode::Char('n') => view.next_match(),
+                        KeyCode::Char('N') => view.previous_match(),
+                        KeyCode::Esc if view.is_loading() => view.cancel_loading(),
+                        KeyCode::Esc if view.is_scanning() => view.cancel_scans(),
+                        KeyCode::Esc if view.has_search() => view.clear_search(),
+                        KeyCode::Char('r') => view.reload(self.selected_file, &self.tx),
+                        KeyCode::Char('d') => view.cycle_delimiter(self.selected_file, &self.tx),
+                        KeyCode::Char('D') => view.reset_delimiter(self.selected_file, &self.tx),
+                        KeyCode::Char('H') => view.toggle_header(self.selected_file, &self.tx),
                         KeyCode::Char('q') | KeyCode::Esc => self.quit(),
                         KeyCode::Char('m') => self.maximized = !self.maximized,
                         KeyCode::Char(';') => self.finding = true,
+                        KeyCode::Char('e') => {
+                            self.diagnostics.set_errors(view.errors());
+                            self.diagnosing = true;
+                        }
+                        KeyCode::Char('i') => {
+                            let (columns, sampled) = view.columns();
+                            self.columns
+                                .set_columns(columns, sampled, view.selected_column());
+                            self.inspecting = true;
+                        }
+                        KeyCode::Char('s') if view.is_database() => self.querying = true,
                         _ => {}
                     }
+                } else if matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+                    self.quit();
                 }
             }
         }
         Ok(())
     }
 
+    /// Files are cycled through in the order they're shown in the tree.
     fn next_file(&mut self) {
-        if self.selected_file < self.file_views.len() - 1 {
-            self.selected_file += 1;
-        } else {
-            self.selected_file = 0;
+        if let Some(file_idx) = self.tree.next_file(self.selected_file) {
+            self.open_file(file_idx);
+            self.tree.reveal(file_idx);
         }
-        self.try_load_file();
     }
 
     fn previous_file(&mut self) {
-        if self.selected_file > 0 {
-            self.selected_file -= 1;
-        } else {
-            self.selected_file = self.file_views.len() - 1;
+        if let Some(file_idx) = self.tree.previous_file(self.selected_file) {
+            self.open_file(file_idx);
+            self.tree.reveal(file_idx);
         }
+    }
+
+    fn open_file(&mut self, file_idx: usize) {
+        self.selected_file = file_idx;
         self.try_load_file();
     }
 
     fn try_load_file(&mut self) {
-        let file = &mut self.file_views[self.selected_file];
-        file.try_load_file();
+        if let Some(file) = self.file_views.get_mut(self.selected_file) {
+            file.try_load_file(self.selected_file, &self.tx);
+        }
     }
 
     fn quit(&mut self) {
@@ -167,7 +488,15 @@ impl Widget for &mut App {
     fn render(self, area: Rect, buf: &mut Buffer) {
         use Constraint::{Length, Min, Percentage};
 
-        if self.maximized {
+        if self.file_views.is_empty() {
+            let [message_area] = Layout::vertical([Length(1)])
+                .flex(layout::Flex::Center)
+                .areas(area);
+            Line::raw("No files found. q:quit")
+                .centered()
+                .style(Color::DarkGray)
+                .render(message_area, buf);
+        } else if self.maximized {
             let layout = Layout::horizontal([Min(0)]);
             let [table_area] = layout.areas(area);
             let file = &mut self.file_views[self.selected_file];
@@ -176,13 +505,20 @@ impl Widget for &mut App {
             let layout = Layout::horizontal([Length(32), Min(0)]);
             let [sidebar_area, table_area] = layout.areas(area);
 
-            let sidebar = Layout::vertical([Percentage(50), Percentage(50), Length(1)]);
-            let [files_area, sheets_area, side_footer] = sidebar.areas(sidebar_area);
+            let sidebar = Layout::vertical([Percentage(50), Percentage(50), Length(2), Length(1)]);
+            let [files_area, sheets_area, info_area, side_footer] = sidebar.areas(sidebar_area);
 
-            self.render_tabs(files_area, buf);
+            self.tree.render(
+                files_area,
+                buf,
+                &self.file_views,
+                self.selected_file,
+                self.browsing,
+            );
 
             let file = &mut self.file_views[self.selected_file];
             file.render_sheet_list(sheets_area, buf);
+            file.render_info(info_area, buf);
 
             render_footer(side_footer, buf);
 
@@ -197,36 +533,58 @@ impl Widget for &mut App {
 
         if self.finding {
             self.finder.render(area, buf);
+        } else if self.diagnosing {
+            self.diagnostics.render(area, buf);
+        } else if self.inspecting {
+            self.columns.render(area, buf);
+        } else if self.querying {
+            self.query.render(area, buf);
+        } else if self.filtering {
+            self.filter.render(area, buf);
         }
     }
 }
 
-impl App {
-    fn render_tabs(&mut self, area: Rect, buf: &mut Buffer) {
-        let titles: Vec<_> = self
-            .file_views
-            .iter()
-            .map(|file| ListItem::from(file.name()))
-            .collect();
-        let highlight_style = (Color::Green, Color::default());
-        let block = Block::new()
-            .padding(Padding::horizontal(1))
-            .border_style(Color::Red)
-            .borders(Borders::BOTTOM)
-            .border_set(symbols::border::PLAIN);
-
-        self.list_state.select(Some(self.selected_file));
-
-        let list = List::new(titles)
-            .block(block)
-            .highlight_style(highlight_style);
-        StatefulWidget::render(list, area, buf, &mut self.list_state);
-    }
-}
-
 fn render_footer(area: Rect, buf: &mut Buffer) {
-    Line::raw("J/K:file <c-j/k>:sheet")
+    Line::raw("J/K:file <c-j/k>:sheet d:delim")
         .centered()
         .style(Style::new().fg(Color::DarkGray).bg(Color::Rgb(18, 18, 18)))
         .render(area, buf);
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::file::LoadOptions;
+
+    use super::*;
+
+    #[test]
+    fn adds_each_new_file_once() {
+        let dir = std::env::temp_dir().join(format!("tabv-watched-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.csv"), "a\n1\n").unwrap();
+        let options = LoadOptions::default();
+        let mut app = App::new(vec![TableFile::new(dir.join("a.csv"), &options)])
+            .watch(vec![dir.clone()], move |_, path| {
+                Some(TableFile::new(path.to_path_buf(), &options))
+            });
+        let paths = |app: &App| -> Vec<_> {
+            app.file_views
+                .iter()
+                .map(|view| view.path().unwrap().to_path_buf())
+                .collect()
+        };
+
+        let canonical = dir.canonicalize().unwrap();
+        std::fs::write(dir.join("b.csv"), "b\n2\n").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a table").unwrap();
+        app.file_changed(&canonical.join("b.csv"));
+        app.file_changed(&canonical.join("b.csv"));
+        app.file_changed(&canonical.join("a.csv"));
+        app.file_changed(&canonical.join("notes.txt"));
+        app.file_changed(&canonical.join("removed.csv"));
+        assert_eq!(paths(&app), [dir.join("a.csv"), dir.join("b.csv")]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,465 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    ops::Range,
+    path::Path,
+    sync::{atomic::Ordering, Mutex},
+};
+
+use arrow::{
+    datatypes::Schema,
+    ipc::{
+        self,
+        reader::{FileReader, StreamReader},
+    },
+    record_batch::RecordBatch,
+    util::display::{ArrayFormatter, FormatOptions},
+};
+use color_eyre::{eyre::eyre, Result};
+use csv::StringRecord;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+use crate::file::{Progress, Records, Rows, Sheets};
+
+/// What Arrow IPC files start with, unlike IPC streams.
+const ARROW_MAGIC: &[u8] = b"ARROW1";
+
+pub fn load_parquet(path: &Path, progress: &Progress) -> Result<Sheets> {
+    let (file, _) = fs_err::File::open(path)?.into_parts();
+    let size = file.metadata()?.len();
+    progress.total_bytes.store(size, Ordering::Relaxed);
+
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+    let n_rows = builder.metadata().file_metadata().num_rows().max(1) as u64;
+    let (headers, types) = columns(builder.schema());
+
+    let mut rows = vec![];
+    for batch in builder.build()? {
+        let batch = batch?;
+        rows.extend(format_rows(&batch, 0..batch.num_rows())?);
+
+        // The row groups are decoded in order, so the share
+        // of rows read is a fair stand-in for bytes read.
+        progress
+            .bytes_read
+            .store(size * rows.len() as u64 / n_rows, Ordering::Relaxed);
+        progress.update(rows.len())?;
+    }
+
+    Ok(vec![(
+        String::new(),
+        Records {
+            headers,
+            types: Some(types),
+            columns: vec![],
+            rows: Rows::Loaded(rows),
+            errors: vec![],
+        },
+    )])
+}
+
+/// Open an Arrow IPC (Feather) file. Only the metadata is read
+/// up front; record batches are read as their rows are needed.
+pub fn load_arrow(path: &Path, progress: &Progress) -> Result<Sheets> {
+    let (mut file, _) = fs_err::File::open(path)?.into_parts();
+    let size = file.metadata()?.len();
+    progress.total_bytes.store(size, Ordering::Relaxed);
+
+    let mut magic = [0; ARROW_MAGIC.len()];
+    let is_stream = file.read_exact(&mut magic).is_err() || magic != ARROW_MAGIC;
+    file.rewind()?;
+    if is_stream {
+        return load_arrow_stream(file, size, progress);
+    }
+
+    let starts: Vec<_> = batch_lengths(&mut file)?
+        .into_iter()
+        .scan(0, |start, len| {
+            let first = *start;
+            *start += len;
+            Some((first, *start))
+        })
+        .collect();
+    let len = starts.last().map(|(_, end)| *end).unwrap_or_default();
+    let starts = starts.into_iter().map(|(start, _)| start).collect();
+
+    let reader = FileReader::try_new_buffered(file, None)?;
+    let (headers, types) = columns(&reader.schema());
+    progress.bytes_read.store(size, Ordering::Relaxed);
+    progress.update(len)?;
+
+    Ok(vec![(
+        String::new(),
+        Records {
+            headers,
+            types: Some(types),
+            columns: vec![],
+            rows: Rows::Arrow(Box::new(ArrowRows {
+                reader: Mutex::new(reader),
+                starts,
+                len,
+                batch: Mutex::default(),
+                error: Mutex::default(),
+            })),
+            errors: vec![],
+        },
+    )])
+}
+
+/// Load an Arrow IPC stream. Streams have no footer saying where
+/// their record batches are, so the batches are all read up front.
+fn load_arrow_stream(file: File, size: u64, progress: &Progress) -> Result<Sheets> {
+    let reader = StreamReader::try_new_buffered(file, None)?;
+    let (headers, types) = columns(&reader.schema());
+
+    let mut rows = vec![];
+    for batch in reader {
+        let batch = batch?;
+        rows.extend(format_rows(&batch, 0..batch.num_rows())?);
+        progress.update(rows.len())?;
+    }
+    progress.bytes_read.store(size, Ordering::Relaxed);
+
+    Ok(vec![(
+        String::new(),
+        Records {
+            headers,
+            types: Some(types),
+            columns: vec![],
+            rows: Rows::Loaded(rows),
+            errors: vec![],
+        },
+    )])
+}
+
+/// Rows of an Arrow IPC file, read a record batch at a time.
+pub struct ArrowRows {
+    reader: Mutex<FileReader<BufReader<File>>>,
+
+    // Index of the first row of each batch.
+    starts: Vec<usize>,
+    len: usize,
+
+    // The last batch read, as (index of the batch, batch).
+    batch: Mutex<Option<(usize, RecordBatch)>>,
+
+    // Why the last read failed, if it did.
+    error: Mutex<Option<String>>,
+}
+impl ArrowRows {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Get the rows in the provided range, reading in the batches
+    /// they're in if need be. If reading fails no rows are returned,
+    /// and the error is kept until a later read succeeds.
+    pub fn rows(&self, range: Range<usize>) -> Vec<StringRecord> {
+        let rows = self.read_rows(range);
+        let mut error = self.error.lock().unwrap();
+        *error = rows.as_ref().err().map(|err| err.to_string());
+        rows.unwrap_or_default()
+    }
+
+    /// Why rows last couldn't be read, if they couldn't.
+    pub fn read_error(&self) -> Option<String> {
+        self.error.lock().unwrap().clone()
+    }
+
+    pub fn read_rows(&self, range: Range<usize>) -> Result<Vec<StringRecord>> {
+        let end = range.end.min(self.len);
+        let mut rows = vec![];
+        let mut idx = range.start;
+        while idx < end {
+            let batch_idx = self.starts.partition_point(|start| *start <= idx) - 1;
+            let batch = self.batch(batch_idx)?;
+            let offset = idx - self.starts[batch_idx];
+            let n_rows = (end - idx).min(batch.num_rows() - offset);
+            rows.extend(format_rows(&batch, offset..offset + n_rows)?);
+            idx += n_rows;
+        }
+        Ok(rows)
+    }
+
+    fn batch(&self, idx: usize) -> Result<RecordBatch> {
+        let mut cached = self.batch.lock().unwrap();
+        if let Some((cached_idx, batch)) = &*cached {
+            if *cached_idx == idx {
+                return Ok(batch.clone());
+            }
+        }
+
+        let mut reader = self.reader.lock().unwrap();
+        reader.set_index(idx)?;
+        let batch = reader
+            .next()
+            .ok_or_else(|| eyre!("Missing record batch {}", idx))??;
+        *cached = Some((idx, batch.clone()));
+        Ok(batch)
+    }
+}
+impl std::fmt::Debug for ArrowRows {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArrowRows")
+            .field("starts", &self.starts)
+            .field("len", &self.len)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The number of rows in each record batch of an Arrow IPC file,
+/// going by the batches' metadata so the data itself isn't read.
+fn batch_lengths(file: &mut File) -> Result<Vec<usize>> {
+    let mut buf = [0; 10];
+    file.seek(SeekFrom::End(-10))?;
+    file.read_exact(&mut buf)?;
+    let footer_len = ipc::reader::read_footer_length(buf)?;
+    let mut footer = vec![0; footer_len];
+    file.seek(SeekFrom::End(-10 - footer_len as i64))?;
+    file.read_exact(&mut footer)?;
+    let footer =
+        ipc::root_as_footer(&footer).map_err(|err| eyre!("Invalid Arrow file footer: {}", err))?;
+
+    footer
+        .recordBatches()
+        .into_iter()
+        .flatten()
+        .map(|block| {
+            let mut meta = vec![0; block.metaDataLength() as usize];
+            file.seek(SeekFrom::Start(block.offset() as u64))?;
+            file.read_exact(&mut meta)?;
+
+            // The message is prefixed by its length, and since
+            // Arrow 0.15 a continuation marker before that, as
+            // skipped by the reader when it parses messages.
+            let prefix = if meta.starts_with(&[0xff; 4]) { 8 } else { 4 };
+            let message = ipc::root_as_message(meta.get(prefix..).unwrap_or_default())
+                .map_err(|err| eyre!("Invalid record batch metadata: {}", err))?;
+            let batch = message
+                .header_as_record_batch()
+                .ok_or_else(|| eyre!("Invalid record batch metadata"))?;
+            Ok(batch.length() as usize)
+        })
+        .collect()
+}
+
+/// The names and types of the columns.
+fn columns(schema: &Schema) -> (StringRecord, StringRecord) {
+    let fields = schema.fields();
+    let headers = fields.iter().map(|field| field.name()).collect();
+    let types = fields
+        .iter()
+        .map(|field| field.data_type().to_string())
+        .collect();
+    (headers, types)
+}
+
+/// Format the rows of the batch in the provided range as text.
+fn format_rows(batch: &RecordBatch, range: Range<usize>) -> Result<Vec<StringRecord>> {
+    let options = FormatOptions::default();
+    let formatters = batch
+        .columns()
+        .iter()
+        .map(|col| ArrayFormatter::try_new(col.as_ref(), &options))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(range
+        .map(|i| {
+            formatters
+                .iter()
+                .map(|formatter| formatter.value(i).to_string())
+                .collect()
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::ipc::writer::{FileWriter, StreamWriter};
+    use arrow::{
+        array::{ArrayRef, Float64Array, Int64Array, StringArray},
+        datatypes::{DataType, Field},
+    };
+    use parquet::arrow::ArrowWriter;
+
+    use super::*;
+    use crate::infer::ColumnType;
+
+    fn write_parquet(name: &str, batches: &[RecordBatch]) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("tabv-{}-{}.parquet", name, std::process::id()));
+        let file = File::create(&path).unwrap();
+        let mut writer = ArrowWriter::try_new(file, batches[0].schema(), None).unwrap();
+        for batch in batches {
+            writer.write(batch).unwrap();
+        }
+        writer.close().unwrap();
+        path
+    }
+
+    fn write_arrow(name: &str, batches: &[RecordBatch], stream: bool) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("tabv-{}-{}.arrow", name, std::process::id()));
+        let file = File::create(&path).unwrap();
+        let schema = batches[0].schema();
+        if stream {
+            let mut writer = StreamWriter::try_new(file, &schema).unwrap();
+            for batch in batches {
+                writer.write(batch).unwrap();
+            }
+            writer.finish().unwrap();
+        } else {
+            let mut writer = FileWriter::try_new(file, &schema).unwrap();
+            for batch in batches {
+                writer.write(batch).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+        path
+    }
+
+    fn batch(ids: Vec<i64>, names: Vec<Option<&str>>, scores: Vec<f64>) -> RecordBatch {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("score", DataType::Float64, false),
+        ]);
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(Int64Array::from(ids)),
+            Arc::new(StringArray::from(names)),
+            Arc::new(Float64Array::from(scores)),
+        ];
+        RecordBatch::try_new(Arc::new(schema), columns).unwrap()
+    }
+
+    fn load(path: &Path) -> Records {
+        let mut sheets = load_parquet(path, &Progress::default()).unwrap();
+        assert_eq!(sheets.len(), 1);
+        let (_, mut records) = sheets.remove(0);
+        records.infer_types().unwrap();
+        records
+    }
+
+    #[test]
+    fn loads_parquet_columns_and_rows() {
+        let path = write_parquet(
+            "columns",
+            &[batch(
+                vec![1, 2, 3],
+                vec![Some("a"), None, Some("c, d")],
+                vec![1.5, -2., 30.25],
+            )],
+        );
+        let records = load(&path);
+
+        assert_eq!(
+            records.headers,
+            StringRecord::from(vec!["id", "name", "score"])
+        );
+        assert_eq!(
+            records.types,
+            Some(StringRecord::from(vec!["Int64", "Utf8", "Float64"]))
+        );
+        let types: Vec<_> = records.columns.iter().map(|column| column.ty).collect();
+        assert_eq!(
+            types,
+            [ColumnType::Int, ColumnType::Text, ColumnType::Float]
+        );
+        assert_eq!(records.columns[1].nulls, 1);
+        assert_eq!(
+            records.window(0..3).into_owned(),
+            [
+                StringRecord::from(vec!["1", "a", "1.5"]),
+                StringRecord::from(vec!["2", "", "-2.0"]),
+                StringRecord::from(vec!["3", "c, d", "30.25"]),
+            ]
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn loads_every_parquet_batch() {
+        let path = write_parquet(
+            "batches",
+            &[
+                batch(vec![1, 2], vec![Some("a"), Some("b")], vec![0., 1.]),
+                batch(vec![3], vec![Some("c")], vec![2.]),
+            ],
+        );
+        let records = load(&path);
+
+        assert_eq!(records.len(), 3);
+        let ids: Vec<_> = records
+            .window(0..3)
+            .iter()
+            .map(|row| row[0].to_string())
+            .collect();
+        assert_eq!(ids, ["1", "2", "3"]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    fn arrow_batches() -> Vec<RecordBatch> {
+        vec![
+            batch(vec![1, 2], vec![Some("a"), Some("b")], vec![0., 1.]),
+            batch(
+                vec![3, 4, 5],
+                vec![Some("c"), None, Some("e")],
+                vec![2., 3., 4.],
+            ),
+            batch(vec![6], vec![Some("f")], vec![5.]),
+        ]
+    }
+
+    fn ids(rows: &[StringRecord]) -> Vec<String> {
+        rows.iter().map(|row| row[0].to_string()).collect()
+    }
+
+    #[test]
+    fn reads_arrow_rows_across_batches() {
+        let path = write_arrow("batches", &arrow_batches(), false);
+        let (_, records) = load_arrow(&path, &Progress::default()).unwrap().remove(0);
+        let Rows::Arrow(rows) = &records.rows else {
+            panic!("expected the rows to be read on demand");
+        };
+
+        assert_eq!(rows.len(), 6);
+        assert_eq!(ids(&rows.rows(1..4)), ["2", "3", "4"]);
+        assert_eq!(ids(&rows.rows(4..6)), ["5", "6"]);
+        assert_eq!(ids(&rows.rows(0..6)), ["1", "2", "3", "4", "5", "6"]);
+        assert_eq!(ids(&rows.rows(5..10)), ["6"]);
+        assert_eq!(&rows.rows(3..4)[0][1], "");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reports_batches_that_cant_be_read() {
+        let path = write_arrow("unreadable", &arrow_batches(), false);
+        let (_, records) = load_arrow(&path, &Progress::default()).unwrap().remove(0);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(16)
+            .unwrap();
+
+        assert!(records.window(0..2).is_empty());
+        assert!(records.read_error().is_some());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reads_arrow_streams() {
+        let path = write_arrow("stream", &arrow_batches(), true);
+        let (_, records) = load_arrow(&path, &Progress::default()).unwrap().remove(0);
+
+        assert_eq!(
+            records.types,
+            Some(StringRecord::from(vec!["Int64", "Utf8", "Float64"]))
+        );
+        assert_eq!(
+            ids(&records.window(0..records.len())),
+            ["1", "2", "3", "4", "5", "6"]
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+}
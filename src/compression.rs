@@ -0,0 +1,150 @@
+use std::{borrow::Cow, fmt, io::Read, path::Path};
+
+/// Compression formats that files are transparently decompressed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+impl Compression {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Some(Compression::Gzip),
+            Some("zst" | "zstd") => Some(Compression::Zstd),
+            Some("bz2") => Some(Compression::Bzip2),
+            _ => None,
+        }
+    }
+
+    /// Wrap a reader so that it produces the decompressed stream.
+    pub fn decompress<'a, R: Read + Send + 'a>(
+        &self,
+        reader: R,
+    ) -> std::io::Result<Box<dyn Read + Send + 'a>> {
+        Ok(match self {
+            Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+            Compression::Zstd => Box::new(zstd::Decoder::new(reader)?),
+            Compression::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(reader)),
+        })
+    }
+}
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Bzip2 => "bzip2",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The path with any compression extension removed,
+/// e.g. `data.csv` for `data.csv.gz`.
+pub fn uncompressed_path(path: &Path) -> Cow<'_, Path> {
+    match Compression::from_path(path) {
+        Some(_) => Cow::Owned(path.with_extension("")),
+        None => Cow::Borrowed(path),
+    }
+}
+
+/// The extension of the file's format, looking past
+/// any compression extension, e.g. `csv` for `data.csv.gz`.
+pub fn format_extension(path: &Path) -> Option<&str> {
+    let name = match Compression::from_path(path) {
+        Some(_) => Path::new(path.file_stem()?),
+        None => path,
+    };
+    name.extension().and_then(|ext| ext.to_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn compress(compression: Compression, data: &[u8]) -> Vec<u8> {
+        match compression {
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+            Compression::Zstd => zstd::encode_all(data, 0).unwrap(),
+            Compression::Bzip2 => {
+                let mut encoder =
+                    bzip2::write::BzEncoder::new(vec![], bzip2::Compression::default());
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+        }
+    }
+
+    fn decompress(compression: Compression, data: &[u8]) -> String {
+        let mut text = String::new();
+        compression
+            .decompress(data)
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        text
+    }
+
+    #[test]
+    fn round_trips_each_format() {
+        let text = "a,b\n1,2\n".repeat(1000);
+        for compression in [Compression::Gzip, Compression::Zstd, Compression::Bzip2] {
+            let compressed = compress(compression, text.as_bytes());
+            assert!(compressed.len() < text.len());
+            assert_eq!(
+                decompress(compression, &compressed),
+                text,
+                "{}",
+                compression
+            );
+        }
+    }
+
+    #[test]
+    fn reads_every_member_of_concatenated_files() {
+        // As made by e.g. `cat a.csv.gz b.csv.gz` or parallel compressors.
+        for compression in [Compression::Gzip, Compression::Zstd, Compression::Bzip2] {
+            let mut compressed = compress(compression, b"a,b\n1,2\n");
+            compressed.extend(compress(compression, b"3,4\n"));
+            assert_eq!(
+                decompress(compression, &compressed),
+                "a,b\n1,2\n3,4\n",
+                "{}",
+                compression
+            );
+        }
+    }
+
+    #[test]
+    fn recognises_compression_extensions() {
+        let from_path = |path: &str| Compression::from_path(Path::new(path));
+        assert_eq!(from_path("data.csv.gz"), Some(Compression::Gzip));
+        assert_eq!(from_path("data.csv.zst"), Some(Compression::Zstd));
+        assert_eq!(from_path("data.csv.zstd"), Some(Compression::Zstd));
+        assert_eq!(from_path("data.csv.bz2"), Some(Compression::Bzip2));
+        assert_eq!(from_path("data.csv"), None);
+        assert_eq!(from_path("gz"), None);
+    }
+
+    #[test]
+    fn strips_compression_extensions() {
+        let uncompressed = |path: &str| uncompressed_path(Path::new(path)).into_owned();
+        assert_eq!(uncompressed("runs/data.csv.gz"), Path::new("runs/data.csv"));
+        assert_eq!(uncompressed("data.csv"), Path::new("data.csv"));
+        assert_eq!(uncompressed("data.gz"), Path::new("data"));
+
+        let ext = |path: &'static str| format_extension(Path::new(path));
+        assert_eq!(ext("runs/data.tsv.gz"), Some("tsv"));
+        assert_eq!(ext("data.parquet"), Some("parquet"));
+        assert_eq!(ext("data.gz"), None);
+        assert_eq!(ext("data"), None);
+    }
+}
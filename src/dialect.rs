@@ -0,0 +1,411 @@
+use std::fmt;
+
+/// Delimiters to try when sniffing, in order of preference.
+pub const DELIMITERS: [u8; 4] = [b',', b'\t', b';', b'|'];
+
+/// Quote characters to try when sniffing, in order of preference.
+const QUOTES: [u8; 2] = [b'"', b'\''];
+
+/// How much of the start of a file to sniff.
+pub const SNIFF_BYTES: u64 = 64 * 1024;
+
+/// How many records of the sample to look at.
+const SNIFF_ROWS: usize = 100;
+
+/// What to call the columns of files without a header.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColumnNames {
+    /// Spreadsheet-style: A, B, ..., Z, AA, AB, ...
+    #[default]
+    Letters,
+
+    /// 1, 2, 3, ...
+    Numbers,
+}
+impl ColumnNames {
+    pub fn name(&self, idx: usize) -> String {
+        match self {
+            ColumnNames::Letters => {
+                let mut idx = idx;
+                let mut name = vec![];
+                loop {
+                    name.push(b'A' + (idx % 26) as u8);
+                    if idx < 26 {
+                        break;
+                    }
+                    idx = idx / 26 - 1;
+                }
+                name.reverse();
+                String::from_utf8(name).unwrap()
+            }
+            ColumnNames::Numbers => (idx + 1).to_string(),
+        }
+    }
+}
+
+/// How the fields of a delimited file are separated and quoted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dialect {
+    pub delimiter: u8,
+    pub quote: u8,
+    pub escape: Option<u8>,
+    pub comment: Option<u8>,
+
+    /// If the first row holds the column names.
+    pub has_header: bool,
+    pub column_names: ColumnNames,
+
+    /// If rows are allowed to have differing numbers of fields.
+    pub flexible: bool,
+}
+impl Default for Dialect {
+    fn default() -> Self {
+        Dialect {
+            delimiter: b',',
+            quote: b'"',
+            escape: None,
+            comment: None,
+            has_header: true,
+            column_names: ColumnNames::default(),
+            flexible: false,
+        }
+    }
+}
+impl fmt::Display for Dialect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "sep {} quote {} {}",
+            display_byte(self.delimiter),
+            display_byte(self.quote),
+            if self.has_header {
+                "header"
+            } else {
+                "no header"
+            }
+        )
+    }
+}
+impl Dialect {
+    /// The default dialect for files with the given extension.
+    pub fn for_extension(ext: Option<&str>) -> Self {
+        let delimiter = match ext {
+            Some("tsv") => b'\t',
+            Some("psv") => b'|',
+            _ => b',',
+        };
+        Dialect {
+            delimiter,
+            ..Default::default()
+        }
+    }
+
+    pub fn reader_builder(&self) -> csv::ReaderBuilder {
+        // Rows with the wrong number of fields are reported
+        // rather than rejected by the reader, so it's always flexible.
+        let mut builder = csv::ReaderBuilder::new();
+        builder
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .escape(self.escape)
+            .double_quote(self.escape.is_none())
+            .comment(self.comment)
+            .has_headers(self.has_header)
+            .flexible(true);
+        builder
+    }
+}
+
+/// Dialect settings given explicitly, e.g. on the command line.
+/// Anything left unset is sniffed from the file, falling back
+/// to the defaults for the file's extension.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DialectOptions {
+    pub delimiter: Option<u8>,
+    pub quote: Option<u8>,
+    pub escape: Option<u8>,
+    pub comment: Option<u8>,
+    pub has_header: Option<bool>,
+    pub column_names: ColumnNames,
+    pub flexible: bool,
+}
+impl DialectOptions {
+    /// Work out the dialect from a sample of the start of the file.
+    pub fn resolve(&self, ext: Option<&str>, sample: &Sample) -> Dialect {
+        let default = Dialect::for_extension(ext);
+
+        // The extension's delimiter is tried first, to be preferred.
+        let delimiters = self.delimiter.map(|d| vec![d]).unwrap_or_else(|| {
+            let mut delimiters = vec![default.delimiter];
+            delimiters.extend(DELIMITERS.iter().filter(|d| **d != default.delimiter));
+            delimiters
+        });
+        let quotes = self.quote.map(|q| vec![q]).unwrap_or(QUOTES.to_vec());
+        let (delimiter, quote) = sample.sniff_delimiter(&delimiters, &quotes).unwrap_or((
+            self.delimiter.unwrap_or(default.delimiter),
+            self.quote.unwrap_or(default.quote),
+        ));
+        let has_header = self
+            .has_header
+            .unwrap_or_else(|| sample.sniff_header(delimiter, quote));
+        Dialect {
+            delimiter,
+            quote,
+            escape: self.escape.or(default.escape),
+            comment: self.comment.or(default.comment),
+            has_header,
+            column_names: self.column_names,
+            flexible: self.flexible || default.flexible,
+        }
+    }
+}
+
+/// The first few KB of a file, used to guess its dialect.
+pub struct Sample {
+    bytes: Vec<u8>,
+
+    // If the file continues past the sample.
+    truncated: bool,
+}
+impl Sample {
+    pub fn new(mut bytes: Vec<u8>) -> Self {
+        let truncated = bytes.len() as u64 >= SNIFF_BYTES;
+        if truncated {
+            // Don't leave a partial row at the end.
+            if let Some(end) = bytes.iter().rposition(|b| *b == b'\n') {
+                bytes.truncate(end + 1);
+            }
+        }
+        Sample { bytes, truncated }
+    }
+
+    fn records(&self, delimiter: u8, quote: u8) -> Vec<csv::StringRecord> {
+        let mut records: Vec<_> = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .quote(quote)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(self.bytes.as_slice())
+            .into_records()
+            .take(SNIFF_ROWS + 1)
+            .filter_map(Result::ok)
+            .collect();
+
+        // The last record may have been cut off mid-way,
+        // e.g. within a quoted field spanning lines.
+        if self.truncated || records.len() > SNIFF_ROWS {
+            records.pop();
+        }
+        records
+    }
+
+    /// Whether any of the usual delimiters split the sample into fields.
+    pub fn is_delimited(&self) -> bool {
+        self.sniff_delimiter(&DELIMITERS, &QUOTES).is_some()
+    }
+
+    /// Find the delimiter and quote character which split the sample
+    /// most consistently into the most fields. The first delimiter is
+    /// preferred: it's kept on ties, and taken outright if it splits
+    /// every row into the same number of fields, as others may split
+    /// the rows into more, e.g. on commas within text.
+    fn sniff_delimiter(&self, delimiters: &[u8], quotes: &[u8]) -> Option<(u8, u8)> {
+        let mut best = None;
+        let mut best_score = 0.;
+        for (i, &delimiter) in delimiters.iter().enumerate() {
+            for &quote in quotes {
+                let records = self.records(delimiter, quote);
+                let Some(n_fields) = modal_len(&records).filter(|n| *n > 1) else {
+                    continue;
+                };
+                let n_consistent = records.iter().filter(|r| r.len() == n_fields).count();
+                if i == 0 && n_consistent == records.len() {
+                    return Some((delimiter, quote));
+                }
+                let consistency = n_consistent as f64 / records.len() as f64;
+                let score = consistency * n_fields as f64;
+                if score > best_score {
+                    best = Some((delimiter, quote));
+                    best_score = score;
+                }
+            }
+        }
+        best
+    }
+
+    /// Guess whether the first row is a header by checking,
+    /// column by column, if it looks different to the rest.
+    /// With no evidence either way we assume there is a header.
+    fn sniff_header(&self, delimiter: u8, quote: u8) -> bool {
+        let records = self.records(delimiter, quote);
+        let Some((header, rows)) = records.split_first() else {
+            return true;
+        };
+
+        let mut votes = 0;
+        for (i, name) in header.iter().enumerate() {
+            let values: Vec<_> = rows
+                .iter()
+                .filter_map(|row| row.get(i))
+                .filter(|val| !val.is_empty())
+                .collect();
+            if values.is_empty() {
+                continue;
+            }
+
+            if values.iter().all(|val| is_number(val)) {
+                votes += if is_number(name) { -1 } else { 1 };
+            } else {
+                let len = values[0].len();
+                if values.iter().all(|val| val.len() == len) {
+                    votes += if name.len() == len { -1 } else { 1 };
+                }
+            }
+        }
+        votes >= 0
+    }
+}
+
+fn modal_len(records: &[csv::StringRecord]) -> Option<usize> {
+    let mut counts: Vec<(usize, usize)> = vec![];
+    for record in records {
+        match counts.iter_mut().find(|(len, _)| *len == record.len()) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((record.len(), 1)),
+        }
+    }
+    counts
+        .into_iter()
+        .max_by_key(|(_, count)| *count)
+        .map(|(len, _)| len)
+}
+
+fn is_number(val: &str) -> bool {
+    val.trim().parse::<f64>().is_ok()
+}
+
+fn display_byte(byte: u8) -> String {
+    match byte {
+        b'\t' => "tab".into(),
+        b' ' => "space".into(),
+        _ => (byte as char).to_string(),
+    }
+}
+
+/// Parse a single-byte character, accepting escapes
+/// and names for characters that are awkward to type.
+pub fn parse_byte(s: &str) -> Result<u8, String> {
+    match s {
+        "\\t" | "tab" => Ok(b'\t'),
+        "space" => Ok(b' '),
+        _ => match s.as_bytes() {
+            [byte] if byte.is_ascii() => Ok(*byte),
+            _ => Err(format!("expected a single ASCII character, got `{}`", s)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sniff(text: &str) -> Dialect {
+        sniff_as("csv", text)
+    }
+
+    fn sniff_as(ext: &str, text: &str) -> Dialect {
+        DialectOptions::default().resolve(Some(ext), &Sample::new(text.as_bytes().to_vec()))
+    }
+
+    #[test]
+    fn sniffs_delimiters() {
+        assert_eq!(sniff("a,b,c\n1,2,3\n").delimiter, b',');
+        assert_eq!(sniff("a\tb\tc\n1\t2\t3\n").delimiter, b'\t');
+        assert_eq!(sniff("a;b;c\n1,5;2,5;3\n").delimiter, b';');
+        assert_eq!(sniff("a|b\n1|2\n").delimiter, b'|');
+
+        // Commas within the text split it into more fields, but
+        // the extension's tab splits it just as consistently.
+        let text = "id\tnote\n1\ta, b, c\n2\td, e, f\n3\tg, h, i\n";
+        assert_eq!(sniff_as("tsv", text).delimiter, b'\t');
+    }
+
+    #[test]
+    fn prefers_the_extensions_delimiter_on_ties() {
+        let text = "a|b,c\n1|2,3\n";
+        assert_eq!(sniff_as("csv", text).delimiter, b',');
+        assert_eq!(sniff_as("psv", text).delimiter, b'|');
+    }
+
+    #[test]
+    fn prefers_the_most_consistent_split() {
+        let text = "name;note\nann;\"x, y, z\"\nbob;\"1, 2\"\ncat;none\n";
+        let dialect = sniff(text);
+        assert_eq!((dialect.delimiter, dialect.quote), (b';', b'"'));
+    }
+
+    #[test]
+    fn sniffs_quotes() {
+        let text = "a,b\n'x, y, z',1\n'u',2\n'v, w',3\n";
+        let dialect = sniff(text);
+        assert_eq!((dialect.delimiter, dialect.quote), (b',', b'\''));
+    }
+
+    #[test]
+    fn falls_back_to_the_extension() {
+        let sample = Sample::new(b"just one column\nof text\n".to_vec());
+        let dialect = DialectOptions::default().resolve(Some("tsv"), &sample);
+        assert_eq!(dialect.delimiter, b'\t');
+        assert!(!sample.is_delimited());
+    }
+
+    #[test]
+    fn keeps_given_options() {
+        let options = DialectOptions {
+            delimiter: Some(b';'),
+            has_header: Some(false),
+            ..Default::default()
+        };
+        let dialect = options.resolve(None, &Sample::new(b"a,b,c\n1,2,3\n".to_vec()));
+        assert_eq!(dialect.delimiter, b';');
+        assert!(!dialect.has_header);
+    }
+
+    #[test]
+    fn sniffs_headers() {
+        assert!(sniff("id,name\n1,ann\n2,bob\n").has_header);
+        assert!(!sniff("1,ann\n2,bob\n3,cat\n").has_header);
+        assert!(sniff("code,n\nAB,1\nCD,2\n").has_header);
+        assert!(!sniff("AB,CD\nEF,GH\nIJ,KL\n").has_header);
+
+        // Without rows to go by, the first row is taken as the header.
+        assert!(sniff("a,b\n").has_header);
+    }
+
+    #[test]
+    fn drops_the_row_cut_off_by_the_sample() {
+        let mut text = "a,b\n".repeat(SNIFF_BYTES as usize / 4);
+        text.push_str("\"x\ny\",z\n");
+        let sample = Sample::new(text.into_bytes());
+        assert!(sample.records(b',', b'"').iter().all(|row| row.len() == 2));
+    }
+
+    #[test]
+    fn names_columns() {
+        let names: Vec<_> = [0, 25, 26, 27, 701, 702]
+            .into_iter()
+            .map(|idx| ColumnNames::Letters.name(idx))
+            .collect();
+        assert_eq!(names, ["A", "Z", "AA", "AB", "ZZ", "AAA"]);
+        assert_eq!(ColumnNames::Numbers.name(0), "1");
+    }
+
+    #[test]
+    fn parses_bytes() {
+        assert_eq!(parse_byte("tab"), Ok(b'\t'));
+        assert_eq!(parse_byte("\\t"), Ok(b'\t'));
+        assert_eq!(parse_byte(";"), Ok(b';'));
+        assert!(parse_byte("ab").is_err());
+        assert!(parse_byte("é").is_err());
+    }
+}
@@ -0,0 +1,131 @@
+use std::io::{self, Read};
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+
+const UTF_8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Guess the encoding of a file from its first bytes.
+///
+/// A byte order mark is used if there is one. Otherwise text that
+/// is valid UTF-8 is taken to be UTF-8, text with lots of zero bytes
+/// to be UTF-16 and anything else to be Windows-1252, which is what
+/// Excel on Windows tends to export.
+pub fn detect(sample: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(sample) {
+        return encoding;
+    }
+    if let Some(encoding) = sniff_utf16(sample) {
+        return encoding;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => UTF_8,
+
+        // The sample may end part-way through a character.
+        Err(err) if err.error_len().is_none() => UTF_8,
+        Err(_) => WINDOWS_1252,
+    }
+}
+
+/// UTF-16 encoded ASCII has every other byte zeroed;
+/// which ones tells us the byte order.
+fn sniff_utf16(sample: &[u8]) -> Option<&'static Encoding> {
+    let n_units = sample.len() / 2;
+    if n_units < 2 {
+        return None;
+    }
+    let even_zeros = sample.iter().step_by(2).filter(|b| **b == 0).count();
+    let odd_zeros = sample
+        .iter()
+        .skip(1)
+        .step_by(2)
+        .filter(|b| **b == 0)
+        .count();
+    if odd_zeros > n_units * 3 / 10 && even_zeros < n_units / 10 {
+        Some(UTF_16LE)
+    } else if even_zeros > n_units * 3 / 10 && odd_zeros < n_units / 10 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Wrap a reader so that it produces UTF-8, without any byte order mark.
+///
+/// UTF-8 input is otherwise passed through as-is so that
+/// invalid bytes can still be flagged when the rows are parsed.
+pub fn decode<'a, R: Read + Send + 'a>(
+    mut reader: R,
+    encoding: &'static Encoding,
+) -> io::Result<Box<dyn Read + Send + 'a>> {
+    if encoding == UTF_8 {
+        let mut start = Vec::with_capacity(UTF_8_BOM.len());
+        (&mut reader)
+            .take(UTF_8_BOM.len() as u64)
+            .read_to_end(&mut start)?;
+        if start == UTF_8_BOM {
+            start.clear();
+        }
+        Ok(Box::new(io::Cursor::new(start).chain(reader)))
+    } else {
+        Ok(Box::new(
+            DecodeReaderBytesBuilder::new()
+                .encoding(Some(encoding))
+                .bom_override(true)
+                .strip_bom(true)
+                .build(reader),
+        ))
+    }
+}
+
+/// Look up an encoding by one of its labels, e.g. `latin1` or `utf-16le`.
+pub fn parse_encoding(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.as_bytes()).ok_or_else(|| format!("unknown encoding `{}`", label))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decoded(bytes: &[u8], encoding: &'static Encoding) -> Vec<u8> {
+        let mut text = vec![];
+        decode(bytes, encoding)
+            .unwrap()
+            .read_to_end(&mut text)
+            .unwrap();
+        text
+    }
+
+    #[test]
+    fn strips_utf8_byte_order_marks() {
+        assert_eq!(decoded(b"\xEF\xBB\xBFa,b\n", UTF_8), b"a,b\n");
+        assert_eq!(decoded(b"a,b\n", UTF_8), b"a,b\n");
+        assert_eq!(decoded(b"\xEF\xBB", UTF_8), b"\xEF\xBB");
+    }
+
+    #[test]
+    fn keeps_invalid_utf8() {
+        assert_eq!(decoded(b"\xEF\xBB\xBFa\xFFb", UTF_8), b"a\xFFb");
+    }
+
+    #[test]
+    fn transcodes_other_encodings() {
+        assert_eq!(decoded(b"caf\xE9", WINDOWS_1252), "café".as_bytes());
+        assert_eq!(decoded(b"\xFF\xFEa\0b\0", UTF_16LE), b"ab");
+    }
+
+    #[test]
+    fn detects_encodings() {
+        assert_eq!(detect(b"\xEF\xBB\xBFabc"), UTF_8);
+        assert_eq!(detect("café".as_bytes()), UTF_8);
+        assert_eq!(detect(b"caf\xE9 au lait"), WINDOWS_1252);
+        let text = "id,name\n1,ann\n2,bob\n";
+        let le: Vec<_> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let be: Vec<_> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(detect(&le), UTF_16LE);
+        assert_eq!(detect(&be), UTF_16BE);
+
+        // A character cut off at the end of the sample.
+        assert_eq!(detect(&"café".as_bytes()[..4]), UTF_8);
+    }
+}
@@ -1,88 +1,766 @@
 use std::{
-    io::{BufRead, BufReader},
-    path::PathBuf,
+    borrow::Cow,
+    fmt,
+    io::{self, BufRead, BufReader, Read},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        mpsc::Sender,
+        Arc, Mutex,
+    },
+    thread,
 };
 
-use color_eyre::Result;
-use csv::StringRecord;
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+use csv::{ByteRecord, StringRecord};
+use encoding_rs::{Encoding, UTF_8};
+
+use crate::{
+    columnar::{load_arrow, load_parquet, ArrowRows},
+    compression::{format_extension, uncompressed_path, Compression},
+    dialect::{Dialect, DialectOptions, Sample, DELIMITERS, SNIFF_BYTES},
+    encoding,
+    fixed_width::{load_fixed_width, spec_path, FixedWidth},
+    index::RowIndex,
+    infer::{infer_columns, ColumnInfo},
+    json::{load_json, load_jsonl},
+    markup::{load_html, load_markdown},
+    spreadsheet::load_workbook,
+    sqlite::{self, load_query, load_sqlite, SqliteRows},
+};
+
+/// Extensions of the files that can be loaded.
+pub const EXTENSIONS: &[&str] = &[
+    "csv", "csvs", "tsv", "psv", "parquet", "xlsx", "xls", "ods", "jsonl", "ndjson", "json", "db",
+    "sqlite", "sqlite3", "fwf", "txt", "dat", "arrow", "feather", "ipc", "md", "markdown", "html",
+    "htm",
+];
+
+/// Extensions of the formats that are read through [`open`], and so
+/// may be compressed. The others are read from disk by their own
+/// readers, e.g. to seek to where the rows are.
+const COMPRESSIBLE: &[&str] = &[
+    "csv", "csvs", "tsv", "psv", "jsonl", "ndjson", "json", "fwf", "txt", "dat", "md", "markdown",
+    "html", "htm",
+];
+
+/// CSV files larger than this (in bytes) are indexed
+/// and read from disk on demand rather than loaded whole.
+const LAZY_THRESHOLD: u64 = 64 * 1024 * 1024;
+
+/// Rows that aren't held in memory are read
+/// this many at a time when going through them all.
+const CHUNK_ROWS: usize = 10_000;
+
+/// Extra rows read on either side of a requested
+/// window so that scrolling doesn't hit the disk
+/// or database on every keypress.
+const WINDOW_PADDING: usize = 128;
+
+#[derive(Debug)]
+pub enum Rows {
+    Loaded(Vec<StringRecord>),
+    Indexed(RowIndex),
+    Sqlite(SqliteRows),
+    Arrow(Box<ArrowRows>),
+}
+
+/// A row that couldn't be parsed cleanly.
+#[derive(Debug, Clone)]
+pub struct RowError {
+    pub row: usize,
+    pub line: u64,
+    pub message: String,
+}
+impl RowError {
+    /// Check a raw record for problems, where `row` is the index
+    /// of the record in its sheet. If `n_fields` is `None` then
+    /// any number of fields is accepted.
+    pub fn check(record: &ByteRecord, n_fields: Option<usize>, row: usize) -> Option<RowError> {
+        let message = if let Some(n_fields) = n_fields.filter(|n| *n != record.len()) {
+            format!("expected {} fields, found {}", n_fields, record.len())
+        } else if let Some(field) = record
+            .iter()
+            .position(|field| std::str::from_utf8(field).is_err())
+        {
+            format!("invalid UTF-8 in field {}", field + 1)
+        } else {
+            return None;
+        };
+        Some(RowError {
+            row,
+            line: record.position().map(|pos| pos.line()).unwrap_or_default(),
+            message,
+        })
+    }
+}
 
 #[derive(Debug)]
 pub struct Records {
     pub headers: StringRecord,
-    pub rows: Vec<StringRecord>,
+
+    /// Column types declared by the file, e.g. from a Parquet schema.
+    pub types: Option<StringRecord>,
+
+    /// Column types inferred from the values, once loaded.
+    pub columns: Vec<ColumnInfo>,
+    pub rows: Rows,
+
+    // Ordered by row.
+    pub errors: Vec<RowError>,
+}
+impl Records {
+    /// The number of rows available so far.
+    /// For indexed records this grows as indexing progresses.
+    pub fn len(&self) -> usize {
+        match &self.rows {
+            Rows::Loaded(rows) => rows.len(),
+            Rows::Indexed(index) => index.len(),
+            Rows::Sqlite(rows) => rows.len(),
+            Rows::Arrow(rows) => rows.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether all the rows are available.
+    pub fn is_complete(&self) -> bool {
+        match &self.rows {
+            Rows::Loaded(_) | Rows::Sqlite(_) | Rows::Arrow(_) => true,
+            Rows::Indexed(index) => index.is_complete(),
+        }
+    }
+
+    /// Whether rows are read on demand rather than held in memory.
+    pub fn is_lazy(&self) -> bool {
+        !matches!(self.rows, Rows::Loaded(_))
+    }
+
+    /// The malformed rows found so far.
+    pub fn errors(&self) -> Cow<'_, [RowError]> {
+        match &self.rows {
+            Rows::Loaded(_) | Rows::Sqlite(_) | Rows::Arrow(_) => Cow::Borrowed(&self.errors),
+            Rows::Indexed(index) => Cow::Owned(index.errors()),
+        }
+    }
+
+    pub fn n_errors(&self) -> usize {
+        match &self.rows {
+            Rows::Loaded(_) | Rows::Sqlite(_) | Rows::Arrow(_) => self.errors.len(),
+            Rows::Indexed(index) => index.n_errors(),
+        }
+    }
+
+    /// Whether the row at `idx` is malformed.
+    pub fn is_flagged(&self, idx: usize) -> bool {
+        match &self.rows {
+            Rows::Loaded(_) | Rows::Sqlite(_) | Rows::Arrow(_) => self
+                .errors
+                .binary_search_by_key(&idx, |err| err.row)
+                .is_ok(),
+            Rows::Indexed(index) => index.is_flagged(idx),
+        }
+    }
+
+    /// Why rows that are read on demand couldn't be read, if they
+    /// couldn't the last time they were needed.
+    pub fn read_error(&self) -> Option<String> {
+        match &self.rows {
+            Rows::Loaded(_) => None,
+            Rows::Indexed(index) => index.read_error(),
+            Rows::Sqlite(rows) => rows.read_error(),
+            Rows::Arrow(rows) => rows.read_error(),
+        }
+    }
+
+    /// Get the rows in the provided range.
+    pub fn window(&self, range: Range<usize>) -> Cow<'_, [StringRecord]> {
+        match &self.rows {
+            Rows::Loaded(rows) => {
+                let end = range.end.min(rows.len());
+                let start = range.start.min(end);
+                Cow::Borrowed(&rows[start..end])
+            }
+            Rows::Indexed(index) => Cow::Owned(index.rows(range)),
+            Rows::Sqlite(rows) => Cow::Owned(rows.rows(range)),
+            Rows::Arrow(rows) => Cow::Owned(rows.rows(range)),
+        }
+    }
+
+    /// Get the rows at the provided indices, in that order.
+    pub fn rows_at(&self, indices: &[usize]) -> Vec<StringRecord> {
+        let mut rows = Vec::with_capacity(indices.len());
+        let mut start = 0;
+        while start < indices.len() {
+            // Consecutive rows are fetched together.
+            let mut end = start + 1;
+            while end < indices.len() && indices[end] == indices[end - 1] + 1 {
+                end += 1;
+            }
+            rows.extend_from_slice(&self.window(indices[start]..indices[end - 1] + 1));
+            start = end;
+        }
+        rows
+    }
+
+    /// Infer the columns' types from their values, going by
+    /// the first rows if they're read on demand.
+    pub fn infer_types(&mut self) -> Result<()> {
+        let rows = match &self.rows {
+            // Rows are indexed in the background, so rather than
+            // wait for enough of them, the first are read directly.
+            Rows::Indexed(index) => Cow::Owned(index.head(INFER_ROWS)?),
+            _ if self.is_lazy() => self.window(0..self.len().min(INFER_ROWS)),
+            _ => self.window(0..self.len()),
+        };
+        self.columns = infer_columns(self.headers.len(), &rows);
+        Ok(())
+    }
+
+    /// Call `f` with the index of each row and the row, in order,
+    /// recording progress and stopping if it's cancelled. Only the
+    /// rows available when this is called are visited, even if
+    /// more are indexed in the meantime.
+    pub fn for_each(
+        &self,
+        progress: &Arc<Progress>,
+        mut f: impl FnMut(usize, &StringRecord),
+    ) -> Result<()> {
+        if let Rows::Sqlite(rows) = &self.rows {
+            return rows.for_each(progress, f);
+        }
+        let len = self.len();
+        for start in (0..len).step_by(CHUNK_ROWS) {
+            let end = (start + CHUNK_ROWS).min(len);
+
+            // Indexed rows are read past the cache kept for
+            // what's on screen, so as not to keep replacing it.
+            let rows = match &self.rows {
+                Rows::Indexed(index) => Cow::Owned(index.read_rows(start, end - start)?),
+                Rows::Arrow(rows) => Cow::Owned(rows.read_rows(start..end)?),
+                _ => self.window(start..end),
+            };
+            for (i, row) in rows.iter().enumerate() {
+                f(start + i, row);
+            }
+            progress.update(end)?;
+        }
+        Ok(())
+    }
+}
+
+/// A cache of the rows around those last requested,
+/// for rows that are read on demand.
+#[derive(Debug, Default)]
+pub struct RowWindow {
+    // Cached rows as (index of the first row, rows).
+    rows: Mutex<(usize, Vec<StringRecord>)>,
+
+    // Why the last read failed, if it did.
+    error: Mutex<Option<String>>,
+}
+impl RowWindow {
+    /// Get the rows in the provided range, out of `len` rows, reading
+    /// them with `read_rows(start, count)` if they aren't cached.
+    /// If reading fails no rows are returned, and the error is kept
+    /// until a later read succeeds.
+    pub fn rows(
+        &self,
+        range: Range<usize>,
+        len: usize,
+        read_rows: impl FnOnce(usize, usize) -> Result<Vec<StringRecord>>,
+    ) -> Vec<StringRecord> {
+        let mut window = self.rows.lock().unwrap();
+        let (first, rows) = &*window;
+        if range.is_empty() {
+            return vec![];
+        } else if range.start >= *first && range.end <= first + rows.len() {
+            return rows[range.start - first..range.end - first].to_vec();
+        }
+
+        let start = range.start.saturating_sub(WINDOW_PADDING);
+        let end = (range.end + WINDOW_PADDING).min(len);
+        let rows = match read_rows(start, end.saturating_sub(start)) {
+            Ok(rows) => {
+                *self.error.lock().unwrap() = None;
+                rows
+            }
+            Err(err) => {
+                *self.error.lock().unwrap() = Some(err.to_string());
+                return vec![];
+            }
+        };
+        let requested = rows
+            .iter()
+            .skip(range.start - start)
+            .take(range.len())
+            .cloned()
+            .collect();
+        *window = (start, rows);
+        requested
+    }
+
+    /// Why the last read failed, if it did.
+    pub fn error(&self) -> Option<String> {
+        self.error.lock().unwrap().clone()
+    }
+}
+
+pub type Sheets = Vec<(String, Records)>;
+
+/// How many rows types are inferred from
+/// for records that are read on demand.
+const INFER_ROWS: usize = 1000;
+
+/// Check for cancellation and report progress every this many rows.
+pub const PROGRESS_INTERVAL: usize = 1000;
+
+/// Progress of a file being loaded in the background.
+#[derive(Debug, Default)]
+pub struct Progress {
+    pub total_bytes: AtomicU64,
+    pub bytes_read: AtomicU64,
+    pub rows_parsed: AtomicUsize,
+    cancelled: AtomicBool,
+}
+impl Progress {
+    /// Record the rows parsed so far,
+    /// failing if loading has been cancelled.
+    pub fn update(&self, rows_parsed: usize) -> Result<()> {
+        self.rows_parsed.store(rows_parsed, Ordering::Relaxed);
+        if self.is_cancelled() {
+            Err(eyre!("Loading cancelled."))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Counts the bytes read from a file towards the loading progress.
+struct ProgressReader<'a, R> {
+    inner: R,
+    progress: &'a Progress,
+}
+impl<R: io::Read> io::Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.progress
+            .bytes_read
+            .fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+/// Why a file couldn't be loaded, and where in the file if known.
+#[derive(Debug)]
+pub struct LoadError {
+    pub message: String,
+    pub line: Option<u64>,
+    pub byte: Option<u64>,
+}
+impl LoadError {
+    fn from_csv(err: &csv::Error) -> Self {
+        let pos = match err.kind() {
+            csv::ErrorKind::Utf8 { pos, .. } | csv::ErrorKind::UnequalLengths { pos, .. } => {
+                pos.as_ref()
+            }
+            csv::ErrorKind::Deserialize { pos, .. } => pos.as_ref(),
+            _ => None,
+        };
+        LoadError {
+            message: err.to_string(),
+            line: pos.map(|pos| pos.line()),
+            byte: pos.map(|pos| pos.byte()),
+        }
+    }
+
+    /// Shift the position for sheets parsed out of a larger file,
+    /// where the sheet starts after `first_line`. The byte offset
+    /// within the sheet isn't meaningful for the file, so it's dropped.
+    fn offset_lines(mut self, first_line: u64) -> Self {
+        self.line = self.line.map(|line| line + first_line);
+        self.byte = None;
+        self
+    }
+}
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+impl std::error::Error for LoadError {}
+impl From<Report> for LoadError {
+    fn from(err: Report) -> Self {
+        match err.downcast::<LoadError>() {
+            Ok(err) => err,
+            Err(err) => match err.downcast_ref::<csv::Error>() {
+                Some(err) => LoadError::from_csv(err),
+                None => LoadError {
+                    message: format!("{:#}", err),
+                    line: None,
+                    byte: None,
+                },
+            },
+        }
+    }
+}
+
+/// The result of loading a file, sent back from the loading thread,
+/// with the progress of that load to tell it apart from later ones.
+pub struct Loaded {
+    pub file_idx: usize,
+    pub progress: Arc<Progress>,
+    pub info: FileInfo,
+    pub result: Result<Sheets>,
+
+    /// Whether this is the result of a query,
+    /// to add to the sheets already loaded.
+    pub query: bool,
+}
+
+/// Details on how a file was parsed.
+#[derive(Debug, Default)]
+pub struct FileInfo {
+    pub dialect: Option<Dialect>,
+    pub encoding: Option<&'static Encoding>,
+    pub compression: Option<Compression>,
+    pub fixed_width: Option<FixedWidth>,
+}
+
+/// How files should be parsed, where anything
+/// left unset is detected from the file itself.
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    pub dialect: DialectOptions,
+    pub encoding: Option<&'static Encoding>,
+
+    /// Column widths for fixed-width files.
+    pub widths: Option<Vec<usize>>,
+}
+
+/// Where a file's data comes from.
+#[derive(Debug, Clone)]
+pub enum Source {
+    Path(PathBuf),
+    Stdin(StdinBuffer),
+}
+impl Source {
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Source::Path(path) => Some(path),
+            Source::Stdin(_) => None,
+        }
+    }
+
+    fn extension(&self) -> Option<&str> {
+        self.path().and_then(supported_extension)
+    }
+}
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Path(path) => write!(f, "{}", path.display()),
+            Source::Stdin(_) => write!(f, "stdin"),
+        }
+    }
+}
+
+/// Standard input, read in full the first time the
+/// file is loaded so that it can be re-parsed later on.
+#[derive(Debug, Clone, Default)]
+pub struct StdinBuffer(Arc<Mutex<Option<Arc<[u8]>>>>);
+impl StdinBuffer {
+    fn read(&self, progress: &Progress) -> io::Result<Arc<[u8]>> {
+        let mut buffer = self.0.lock().unwrap();
+        if let Some(data) = buffer.as_ref() {
+            return Ok(data.clone());
+        }
+
+        let mut data = vec![];
+        ProgressReader {
+            inner: io::stdin().lock(),
+            progress,
+        }
+        .read_to_end(&mut data)?;
+        let data: Arc<[u8]> = data.into();
+        *buffer = Some(data.clone());
+        Ok(data)
+    }
 }
 
 #[derive(Debug)]
 pub struct TableFile {
     pub name: String,
-    pub path: PathBuf,
-    pub records: Option<Vec<(String, Records)>>,
+    pub source: Source,
+    /// The sheets loaded, shared with any scans of their rows.
+    pub records: Option<Vec<(String, Arc<Records>)>>,
+    pub options: LoadOptions,
+    pub info: FileInfo,
+    pub progress: Option<Arc<Progress>>,
+    pub error: Option<LoadError>,
+
+    /// SQL queries run against a database, whose results
+    /// are kept as sheets when the file is reloaded, and how
+    /// many of them have finished running.
+    pub queries: Vec<String>,
+    loaded_queries: usize,
 }
 impl TableFile {
-    pub fn new(path: PathBuf) -> Self {
-        let name = path
+    pub fn new(path: PathBuf, options: &LoadOptions) -> Self {
+        let name = uncompressed_path(&path)
             .file_stem()
-            .expect("No valid file stem")
+            .unwrap_or(path.as_os_str())
             .to_string_lossy()
             .to_string();
         TableFile {
             name,
-            path,
+            source: Source::Path(path),
+            options: options.clone(),
+            info: FileInfo::default(),
             records: None,
+            progress: None,
+            error: None,
+            queries: vec![],
+            loaded_queries: 0,
         }
     }
 
-    pub fn load(&mut self) -> Result<()> {
-        match self.path.extension().and_then(|ext| ext.to_str()) {
-            Some("csv") => self.load_csv(),
-            Some("csvs") => self.load_csvs(),
-            _ => panic!("Unrecognized extension. Should be either `csv` or `csvs`."),
+    /// A file read from standard input.
+    pub fn stdin(options: &LoadOptions) -> Self {
+        TableFile {
+            name: "stdin".into(),
+            source: Source::Stdin(StdinBuffer::default()),
+            options: options.clone(),
+            info: FileInfo::default(),
+            records: None,
+            progress: None,
+            error: None,
+            queries: vec![],
+            loaded_queries: 0,
         }
     }
 
-    fn load_csv(&mut self) -> Result<()> {
-        let mut rdr = csv::Reader::from_path(&self.path)?;
-        let headers = rdr.headers()?.clone();
-        let rows: Vec<StringRecord> = rdr.into_records().filter_map(Result::ok).collect();
-        let records = vec![(String::new(), Records { headers, rows })];
-        self.records = Some(records);
-        Ok(())
-    }
+    /// Load the file on a background thread,
+    /// sending the result to `tx` once it's done.
+    pub fn load(&mut self, file_idx: usize, tx: Sender<Loaded>) {
+        let progress = Arc::new(Progress::default());
+        self.progress = Some(progress.clone());
+        self.error = None;
 
-    fn load_csvs(&mut self) -> Result<()> {
-        let file = fs_err::File::open(&self.path)?;
-        let reader = BufReader::new(file);
+        let source = self.source.clone();
+        let options = self.options.clone();
+        let queries = self.queries.clone();
+        thread::spawn(move || {
+            let mut info = FileInfo::default();
+            let result = match &source {
+                Source::Path(path) => match supported_extension(path) {
+                    Some("csv" | "tsv" | "psv") => {
+                        load_csv(&source, &options, &mut info, &progress)
+                    }
+                    Some("csvs") => load_csvs(&source, &options, &mut info, &progress),
+                    Some("parquet") => load_parquet(path, &progress),
+                    Some("arrow" | "feather" | "ipc") => load_arrow(path, &progress),
+                    Some("xlsx" | "xls" | "ods") => load_workbook(path, &options, &progress),
+                    Some("db" | "sqlite" | "sqlite3") => load_sqlite(path, &queries, &progress),
+                    Some("jsonl" | "ndjson") => load_jsonl(&source, &options, &mut info, &progress),
+                    Some("json") => load_json(&source, &options, &mut info, &progress),
+                    Some("md" | "markdown") => {
+                        load_markdown(&source, &options, &mut info, &progress)
+                    }
+                    Some("html" | "htm") => load_html(&source, &options, &mut info, &progress),
+                    Some("fwf" | "txt" | "dat") => {
+                        load_fixed_width(&source, &options, &mut info, &progress)
+                    }
+                    _ => Err(eyre!(
+                        "Unrecognized extension. Should be one of: {}.",
+                        EXTENSIONS.join(", ")
+                    )),
+                },
 
-        let mut buffers: Vec<(String, Vec<String>)> = vec![];
-        let mut buffer = vec![];
-        let mut name = String::new();
+                Source::Stdin(buffer) => load_stdin(buffer, &options, &mut info, &progress),
+            };
 
-        for line in reader.lines() {
-            let line = line?;
-            if line.starts_with("#>") {
-                if !buffer.is_empty() {
-                    buffers.push((name, buffer.drain(..).collect()));
+            let result = result.and_then(|mut sheets| {
+                for (_, records) in &mut sheets {
+                    records.infer_types()?;
                 }
-                name = line.chars().skip(2).take_while(|c| *c != ',').collect();
-            } else {
-                buffer.push(line);
+                Ok(sheets)
+            });
+
+            // Nobody is waiting on a cancelled load.
+            if !progress.is_cancelled() {
+                let _ = tx.send(Loaded {
+                    file_idx,
+                    progress,
+                    info,
+                    result,
+                    query: false,
+                });
             }
+        });
+    }
+
+    pub fn finish_loading(&mut self, info: FileInfo, result: Result<Sheets>) {
+        self.progress = None;
+        self.info = info;
+        match result {
+            Ok(sheets) => {
+                let sheets = sheets
+                    .into_iter()
+                    .map(|(name, records)| (name, Arc::new(records)))
+                    .collect();
+                self.records = Some(sheets);
+            }
+            Err(err) => self.error = Some(err.into()),
         }
-        if !buffer.is_empty() {
-            buffers.push((name, buffer.drain(..).collect()));
+    }
+
+    /// Add the result of a query as a sheet. A query that
+    /// fails to run is dropped, with the other sheets kept.
+    pub fn finish_query(&mut self, result: Result<Sheets>) -> Result<()> {
+        self.progress = None;
+        match (result, &mut self.records) {
+            (Ok(sheets), Some(records)) => {
+                records.extend(
+                    sheets
+                        .into_iter()
+                        .map(|(name, records)| (name, Arc::new(records))),
+                );
+                self.loaded_queries = self.queries.len();
+                Ok(())
+            }
+            (result, _) => {
+                self.queries.truncate(self.loaded_queries);
+                result.map(|_| ())
+            }
         }
+    }
 
-        let records: Result<_> = buffers
-            .into_iter()
-            .map(|(name, buf)| {
-                let buf = buf.join("\n").into_bytes();
-                let mut rdr = csv::Reader::from_reader(buf.as_slice());
-                let headers = rdr.headers()?.clone();
-                let rows: Vec<StringRecord> = rdr.into_records().filter_map(Result::ok).collect();
-                Ok((name, Records { headers, rows }))
-            })
-            .collect();
-        self.records = Some(records?);
+    /// Stop loading the file, dropping any query that was
+    /// being run rather than running it on the next load.
+    pub fn cancel_loading(&mut self) {
+        if let Some(progress) = self.progress.take() {
+            progress.cancel();
+            self.queries.truncate(self.loaded_queries);
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.progress.is_some()
+    }
+
+    /// Whether `progress` is that of the current load,
+    /// rather than one that was cancelled or replaced since.
+    pub fn is_current_load(&self, progress: &Arc<Progress>) -> bool {
+        self.progress
+            .as_ref()
+            .is_some_and(|current| Arc::ptr_eq(current, progress))
+    }
+
+    /// Re-parse with the next delimiter in place of the current one.
+    pub fn cycle_delimiter(&mut self) {
+        let current = self
+            .info
+            .dialect
+            .map(|dialect| dialect.delimiter)
+            .or(self.options.dialect.delimiter);
+        let next = current
+            .and_then(|current| DELIMITERS.iter().position(|d| *d == current))
+            .map(|i| DELIMITERS[(i + 1) % DELIMITERS.len()])
+            .unwrap_or(DELIMITERS[0]);
+        self.options.dialect.delimiter = Some(next);
+    }
+
+    /// Go back to sniffing the delimiter.
+    pub fn reset_delimiter(&mut self) {
+        self.options.dialect.delimiter = None;
+    }
+
+    /// Re-parse with the first row treated as the header
+    /// if it currently isn't, or as data if it currently is.
+    pub fn toggle_header(&mut self) {
+        let has_header = self
+            .info
+            .dialect
+            .map(|dialect| dialect.has_header)
+            .or(self.info.fixed_width.as_ref().map(|fw| fw.has_header))
+            .or(self.options.dialect.has_header)
+            .unwrap_or(true);
+        self.options.dialect.has_header = Some(!has_header);
+    }
+
+    /// Whether the file has one of the supported extensions,
+    /// followed by a compression extension if it can be compressed.
+    pub fn is_supported(path: &Path) -> bool {
+        supported_extension(path).is_some()
+    }
+
+    /// Whether to pick up the file when looking through a directory.
+    /// Plain text files are only taken for tables if they have a
+    /// spec giving their column widths, and documents, which mostly
+    /// hold no tables, only when opened by name.
+    pub fn is_discoverable(path: &Path) -> bool {
+        match supported_extension(path) {
+            Some("txt" | "dat") => spec_path(path).is_file(),
+            Some("md" | "markdown" | "html" | "htm") => false,
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    pub fn is_database(&self) -> bool {
+        matches!(self.source.extension(), Some("db" | "sqlite" | "sqlite3"))
+    }
+
+    /// Run an SQL query against the database in the background,
+    /// sending its result to `tx` to be added as a new sheet.
+    /// The query is kept to run again if the database is reloaded.
+    pub fn add_query(&mut self, sql: &str, file_idx: usize, tx: Sender<Loaded>) -> Result<()> {
+        let (Some(path), Some(_)) = (self.source.path(), &self.records) else {
+            return Err(eyre!("The database isn't loaded."));
+        };
+        let path = path.to_path_buf();
+
+        // Mistakes in the query are caught before it's run.
+        sqlite::check_query(&path, sql)?;
+        self.cancel_loading();
+        let progress = Arc::new(Progress::default());
+        self.progress = Some(progress.clone());
+        self.queries.push(sql.to_string());
+
+        let sql = sql.to_string();
+        let idx = self.queries.len() - 1;
+        thread::spawn(move || {
+            let result = load_query(&path, &sql, idx, &progress).and_then(|mut sheets| {
+                for (_, records) in &mut sheets {
+                    records.infer_types()?;
+                }
+                Ok(sheets)
+            });
+            if !progress.is_cancelled() {
+                let _ = tx.send(Loaded {
+                    file_idx,
+                    progress,
+                    info: FileInfo::default(),
+                    result,
+                    query: true,
+                });
+            }
+        });
         Ok(())
     }
 
@@ -93,3 +771,382 @@ impl TableFile {
             .unwrap_or_default()
     }
 }
+
+/// The extension of the file's format, looking past any compression
+/// extension, if it's one that can be loaded compressed or not.
+fn supported_extension(path: &Path) -> Option<&str> {
+    let ext = format_extension(path).filter(|ext| EXTENSIONS.contains(ext))?;
+    let compressed = Compression::from_path(path).is_some();
+    (!compressed || COMPRESSIBLE.contains(&ext)).then_some(ext)
+}
+
+/// Piped data has no name to go by, so it's taken to be JSON
+/// if it looks like it, and otherwise delimited text unless
+/// there's no sign of a delimiter, as with `ps` or `df` output.
+fn load_stdin(
+    buffer: &StdinBuffer,
+    options: &LoadOptions,
+    info: &mut FileInfo,
+    progress: &Progress,
+) -> Result<Sheets> {
+    let source = Source::Stdin(buffer.clone());
+    let data = buffer.read(progress)?;
+    let sample = &data[..data.len().min(SNIFF_BYTES as usize)];
+    let fixed_width = options.widths.is_some()
+        || (options.dialect.delimiter.is_none() && !Sample::new(sample.to_vec()).is_delimited());
+    match data.iter().find(|b| !b.is_ascii_whitespace()) {
+        Some(b'[' | b'{') => load_json(&source, options, info, progress),
+        _ if fixed_width => load_fixed_width(&source, options, info, progress),
+        _ => load_csv(&source, options, info, progress),
+    }
+}
+
+type Reader<'a> = Box<dyn io::Read + Send + 'a>;
+
+/// Open the file, decompressing it if need be and detecting
+/// its encoding if it wasn't given, and return a reader
+/// that produces it as UTF-8.
+pub fn open<'a>(
+    source: &Source,
+    options: &LoadOptions,
+    info: &mut FileInfo,
+    progress: &'a Progress,
+) -> Result<Reader<'a>> {
+    let reader: Reader = match source {
+        Source::Path(path) => {
+            let file = fs_err::File::open(path)?;
+            progress
+                .total_bytes
+                .store(file.metadata()?.len(), Ordering::Relaxed);
+
+            // Progress is tracked in terms of the bytes on disk,
+            // so count them before decompressing.
+            let reader = ProgressReader {
+                inner: file,
+                progress,
+            };
+            info.compression = Compression::from_path(path);
+            match info.compression {
+                Some(compression) => compression.decompress(reader)?,
+                None => Box::new(reader),
+            }
+        }
+        Source::Stdin(buffer) => {
+            // Once buffered, track progress through parsing it.
+            let data = buffer.read(progress)?;
+            progress
+                .total_bytes
+                .store(data.len() as u64, Ordering::Relaxed);
+            progress.bytes_read.store(0, Ordering::Relaxed);
+            Box::new(ProgressReader {
+                inner: io::Cursor::new(data),
+                progress,
+            })
+        }
+    };
+
+    let (encoding, reader) = match options.encoding {
+        Some(encoding) => (encoding, reader),
+        None => {
+            let (sample, reader) = peek(reader)?;
+            (encoding::detect(&sample), reader)
+        }
+    };
+    info.encoding = Some(encoding);
+    Ok(encoding::decode(reader, encoding)?)
+}
+
+/// Read the start of a stream without consuming it.
+fn peek(mut reader: Reader<'_>) -> io::Result<(Vec<u8>, Reader<'_>)> {
+    let mut sample = vec![];
+    (&mut reader).take(SNIFF_BYTES).read_to_end(&mut sample)?;
+    let reader = io::Cursor::new(sample.clone()).chain(reader);
+    Ok((sample, Box::new(reader)))
+}
+
+fn load_csv(
+    source: &Source,
+    options: &LoadOptions,
+    info: &mut FileInfo,
+    progress: &Progress,
+) -> Result<Sheets> {
+    let (sample, reader) = peek(open(source, options, info, progress)?)?;
+    let dialect = options
+        .dialect
+        .resolve(source.extension(), &Sample::new(sample));
+    info.dialect = Some(dialect);
+    let dialect = &dialect;
+
+    // Byte offsets are only meaningful if the file
+    // is read as-is, i.e. not decompressed or transcoded.
+    let size = progress.total_bytes.load(Ordering::Relaxed);
+    let lazy_path = source.path().filter(|_| {
+        size > LAZY_THRESHOLD && info.compression.is_none() && info.encoding == Some(UTF_8)
+    });
+    let (headers, rows, errors) = if let Some(path) = lazy_path {
+        let (headers, index) = RowIndex::build(path, dialect)?;
+        (headers, Rows::Indexed(index), vec![])
+    } else {
+        let mut rdr = dialect.reader_builder().from_reader(reader);
+        let headers = read_headers(&mut rdr, dialect)?;
+        let (rows, errors) = read_rows(&mut rdr, dialect, |rows| progress.update(rows))?;
+        (headers, Rows::Loaded(rows), errors)
+    };
+    Ok(vec![(
+        String::new(),
+        Records {
+            headers,
+            types: None,
+            columns: vec![],
+            rows,
+            errors,
+        },
+    )])
+}
+
+fn load_csvs(
+    source: &Source,
+    options: &LoadOptions,
+    info: &mut FileInfo,
+    progress: &Progress,
+) -> Result<Sheets> {
+    let reader = BufReader::new(open(source, options, info, progress)?);
+
+    // Sheets as (name, line the sheet starts on, lines).
+    let mut buffers: Vec<(String, u64, Vec<Vec<u8>>)> = vec![];
+    let mut buffer = vec![];
+    let mut name = String::new();
+    let mut first_line = 0;
+
+    // Lines are kept as bytes so that invalid UTF-8
+    // is flagged per row rather than failing the file.
+    for (i, line) in reader.split(b'\n').enumerate() {
+        let mut line = line.map_err(|err| LoadError {
+            message: err.to_string(),
+            line: Some(i as u64 + 1),
+            byte: None,
+        })?;
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        if i % PROGRESS_INTERVAL == 0 {
+            progress.update(0)?;
+        }
+        if let Some(sheet_name) = line.strip_prefix(b"#>") {
+            if !buffer.is_empty() {
+                buffers.push((name, first_line, std::mem::take(&mut buffer)));
+            }
+            name = String::from_utf8_lossy(sheet_name).to_string();
+            first_line = i as u64 + 1;
+        } else {
+            buffer.push(line);
+        }
+    }
+    if !buffer.is_empty() {
+        buffers.push((name, first_line, std::mem::take(&mut buffer)));
+    }
+
+    // Sniff the dialect from the start of the sheets, minus their names.
+    let mut sample = vec![];
+    for line in buffers.iter().flat_map(|(_, _, buf)| buf) {
+        if sample.len() as u64 >= SNIFF_BYTES {
+            break;
+        }
+        sample.extend_from_slice(line);
+        sample.push(b'\n');
+    }
+    let dialect = options.dialect.resolve(Some("csv"), &Sample::new(sample));
+    info.dialect = Some(dialect);
+    let dialect = &dialect;
+
+    let mut rows_parsed = 0;
+    buffers
+        .into_iter()
+        .map(|(name, first_line, buf)| {
+            // Sheet names may be padded out with empty fields.
+            let name = name
+                .split(dialect.delimiter as char)
+                .next()
+                .unwrap_or_default()
+                .to_string();
+
+            let buf = buf.join(&b'\n');
+            let mut rdr = dialect.reader_builder().from_reader(buf.as_slice());
+            let headers = read_headers(&mut rdr, dialect)
+                .map_err(|err| LoadError::from_csv(&err).offset_lines(first_line))?;
+            let (rows, mut errors) = read_rows(&mut rdr, dialect, |rows| {
+                progress.update(rows_parsed + rows)
+            })
+            .map_err(|err| LoadError::from(err).offset_lines(first_line))?;
+            for err in &mut errors {
+                err.line += first_line;
+            }
+            rows_parsed += rows.len();
+            let rows = Rows::Loaded(rows);
+            Ok((
+                name,
+                Records {
+                    headers,
+                    types: None,
+                    columns: vec![],
+                    rows,
+                    errors,
+                },
+            ))
+        })
+        .collect()
+}
+
+/// The headers of the file, or generated column names if it doesn't have any.
+pub fn read_headers<R: io::Read>(
+    rdr: &mut csv::Reader<R>,
+    dialect: &Dialect,
+) -> csv::Result<StringRecord> {
+    if dialect.has_header {
+        Ok(rdr.headers()?.clone())
+    } else {
+        let n_fields = rdr.byte_headers()?.len();
+        Ok((0..n_fields)
+            .map(|idx| dialect.column_names.name(idx))
+            .collect())
+    }
+}
+
+/// Read all the remaining rows, periodically calling `on_progress`
+/// with the number of rows read so far. Reading is aborted
+/// if `on_progress` returns an error.
+///
+/// Malformed rows are kept (lossily decoded, if need be)
+/// and reported alongside the rows.
+fn read_rows<R: io::Read>(
+    rdr: &mut csv::Reader<R>,
+    dialect: &Dialect,
+    mut on_progress: impl FnMut(usize) -> Result<()>,
+) -> Result<(Vec<StringRecord>, Vec<RowError>)> {
+    let n_fields = Some(rdr.byte_headers()?.len()).filter(|_| !dialect.flexible);
+
+    let mut rows = vec![];
+    let mut errors = vec![];
+    let mut record = ByteRecord::new();
+    loop {
+        if rows.len() % PROGRESS_INTERVAL == 0 {
+            on_progress(rows.len())?;
+        }
+        if !rdr.read_byte_record(&mut record)? {
+            break;
+        }
+        if let Some(err) = RowError::check(&record, n_fields, rows.len()) {
+            errors.push(err);
+        }
+        rows.push(StringRecord::from_byte_record_lossy(record.clone()));
+    }
+    Ok((rows, errors))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use super::*;
+
+    fn piped(text: &str) -> (Sheets, FileInfo) {
+        let buffer = StdinBuffer(Arc::new(Mutex::new(Some(text.as_bytes().into()))));
+        let mut info = FileInfo::default();
+        let options = LoadOptions::default();
+        let sheets = load_stdin(&buffer, &options, &mut info, &Progress::default()).unwrap();
+        (sheets, info)
+    }
+
+    /// The headers and then the rows of the only sheet,
+    /// once they've all been indexed.
+    fn contents(sheets: &Sheets) -> Vec<StringRecord> {
+        assert_eq!(sheets.len(), 1);
+        let records = &sheets[0].1;
+        while !records.is_complete() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let mut rows = vec![records.headers.clone()];
+        rows.extend(records.window(0..records.len()).iter().cloned());
+        rows
+    }
+
+    #[test]
+    fn reads_piped_text_like_a_file() {
+        for (ext, text) in [
+            ("csv", "name;score\nann;\"1,5\"\nbob;2\n"),
+            ("tsv", "name\tscore\nann\t1\n"),
+            ("csv", "1,ann\n2,bob\n3,cat\n"),
+        ] {
+            let path =
+                std::env::temp_dir().join(format!("tabv-piped-{}.{}", std::process::id(), ext));
+            std::fs::write(&path, text).unwrap();
+            let mut info = FileInfo::default();
+            let options = LoadOptions::default();
+            let sheets = load_csv(
+                &Source::Path(path.clone()),
+                &options,
+                &mut info,
+                &Progress::default(),
+            )
+            .unwrap();
+
+            let (piped_sheets, piped_info) = piped(text);
+            assert_eq!(contents(&piped_sheets), contents(&sheets), "{}", text);
+            assert_eq!(piped_info.dialect, info.dialect, "{}", text);
+            assert!(piped_info.dialect.is_some());
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn reads_piped_json() {
+        let (sheets, info) = piped("  [{\"a\": 1}, {\"a\": 2, \"b\": {\"c\": 3}}]");
+        assert_eq!(
+            contents(&sheets),
+            [
+                StringRecord::from(vec!["a", "b.c"]),
+                StringRecord::from(vec!["1", ""]),
+                StringRecord::from(vec!["2", "3"]),
+            ]
+        );
+        assert!(info.dialect.is_none());
+
+        let (sheets, _) = piped("{\"a\": 1}\n{\"a\": 2}\n");
+        assert_eq!(contents(&sheets).len(), 3);
+    }
+
+    #[test]
+    fn reads_piped_text_without_delimiters_as_fixed_width() {
+        let (sheets, info) = piped("PID  CMD\n1    init\n22   sh -c\n");
+        assert!(info.fixed_width.is_some());
+        assert_eq!(
+            contents(&sheets)[2],
+            StringRecord::from(vec!["22", "sh -c"])
+        );
+    }
+
+    #[test]
+    fn recognises_compressed_files_read_as_streams() {
+        for path in [
+            "a.csv",
+            "a.csv.gz",
+            "a.jsonl.zst",
+            "a.md.bz2",
+            "a.parquet",
+            "a.db",
+        ] {
+            assert!(TableFile::is_supported(Path::new(path)), "{}", path);
+        }
+        for path in [
+            "a.parquet.gz",
+            "a.feather.gz",
+            "a.xlsx.bz2",
+            "a.db.zst",
+            "a.gz",
+            "a.exe",
+        ] {
+            assert!(!TableFile::is_supported(Path::new(path)), "{}", path);
+            assert!(!TableFile::is_discoverable(Path::new(path)), "{}", path);
+        }
+    }
+}
@@ -0,0 +1,535 @@
+//! Expressions for filtering rows, e.g.
+//! `status == "error" && latency > 250` or `host ~ /^db-/`.
+//!
+//! Columns are referred to by name, or in backticks if their name
+//! has spaces or the like. Values are compared as numbers or dates
+//! where they look like them, and `~` matches a regular expression
+//! (`/.../i` to ignore case). Conditions are combined with
+//! `&&`, `||` and `!`, and grouped with parentheses.
+
+use std::cmp::Ordering;
+
+use color_eyre::{eyre::eyre, Result};
+use csv::StringRecord;
+use regex::{Regex, RegexBuilder};
+
+use crate::{file::Records, value::Value};
+
+/// How many rows to look at when checking
+/// what kind of values a column holds.
+const CHECK_ROWS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Match,
+    NotMatch,
+}
+impl Op {
+    fn test(self, ordering: Ordering) -> bool {
+        match self {
+            Op::Eq => ordering.is_eq(),
+            Op::Ne => ordering.is_ne(),
+            Op::Lt => ordering.is_lt(),
+            Op::Le => ordering.is_le(),
+            Op::Gt => ordering.is_gt(),
+            Op::Ge => ordering.is_ge(),
+            Op::Match | Op::NotMatch => false,
+        }
+    }
+
+    /// The same comparison with the sides swapped.
+    fn flip(self) -> Self {
+        match self {
+            Op::Lt => Op::Gt,
+            Op::Le => Op::Ge,
+            Op::Gt => Op::Lt,
+            Op::Ge => Op::Le,
+            op => op,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Op::Eq => "==",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Match => "~",
+            Op::NotMatch => "!~",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Text(String),
+    Number(f64),
+    Regex(String, bool),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+#[derive(Debug)]
+enum Operand {
+    Column(usize),
+    Number(f64),
+    Text(String),
+    Regex(Regex),
+}
+
+#[derive(Debug)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare { col: usize, op: Op, rhs: Operand },
+}
+
+/// A parsed filter expression, with its columns
+/// resolved against a sheet's headers.
+#[derive(Debug)]
+pub struct Filter {
+    source: String,
+    expr: Expr,
+}
+impl Filter {
+    pub fn parse(input: &str, headers: &StringRecord) -> Result<Self> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            headers,
+        };
+        let expr = parser.or()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(Filter {
+                source: input.to_string(),
+                expr,
+            }),
+            Some(token) => Err(eyre!("Unexpected {}", describe(token))),
+        }
+    }
+
+    /// The expression as it was typed.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Check that the columns compared with numbers or
+    /// dates hold those, going by the first rows.
+    pub fn check(&self, records: &Records) -> Result<()> {
+        let sample = records.window(0..CHECK_ROWS);
+        check(&self.expr, &records.headers, &sample)
+    }
+
+    pub fn matches(&self, row: &StringRecord) -> bool {
+        eval(&self.expr, row)
+    }
+}
+
+fn check(expr: &Expr, headers: &StringRecord, sample: &[StringRecord]) -> Result<()> {
+    match expr {
+        Expr::And(a, b) | Expr::Or(a, b) => {
+            check(a, headers, sample)?;
+            check(b, headers, sample)
+        }
+        Expr::Not(a) => check(a, headers, sample),
+        Expr::Compare { col, op, rhs } => {
+            // Text is only compared as a value when ordering.
+            let (expected, kind, literal) = match rhs {
+                Operand::Number(number) => (Value::Number(*number), "numbers", number.to_string()),
+                Operand::Text(text) if !matches!(op, Op::Eq | Op::Ne) => match Value::parse(text) {
+                    value @ Value::DateTime(_) => (value, "dates", format!("\"{}\"", text)),
+                    _ => return Ok(()),
+                },
+                _ => return Ok(()),
+            };
+            let mut values = sample
+                .iter()
+                .map(|row| Value::parse(row.get(*col).unwrap_or_default()))
+                .filter(|value| *value != Value::Null)
+                .peekable();
+            let any_values = values.peek().is_some();
+            let holds = values
+                .any(|value| std::mem::discriminant(&value) == std::mem::discriminant(&expected));
+            if !any_values || holds {
+                Ok(())
+            } else {
+                Err(eyre!(
+                    "`{}` has no {} to compare with {}",
+                    headers.get(*col).unwrap_or_default(),
+                    kind,
+                    literal
+                ))
+            }
+        }
+    }
+}
+
+fn eval(expr: &Expr, row: &StringRecord) -> bool {
+    match expr {
+        Expr::And(a, b) => eval(a, row) && eval(b, row),
+        Expr::Or(a, b) => eval(a, row) || eval(b, row),
+        Expr::Not(a) => !eval(a, row),
+        Expr::Compare { col, op, rhs } => {
+            let cell = row.get(*col).unwrap_or_default();
+            match rhs {
+                Operand::Regex(regex) => regex.is_match(cell) == (*op == Op::Match),
+
+                // Text is matched exactly rather than as a value,
+                // so that e.g. `code == "007"` doesn't match `7`.
+                Operand::Text(text) if matches!(op, Op::Eq | Op::Ne) => {
+                    (cell == text) == (*op == Op::Eq)
+                }
+                Operand::Text(text) => compare(cell, text, *op),
+                Operand::Number(number) => match Value::parse(cell) {
+                    Value::Number(value) => op.test(value.total_cmp(number)),
+                    _ => *op == Op::Ne,
+                },
+                Operand::Column(other) => compare(cell, row.get(*other).unwrap_or_default(), *op),
+            }
+        }
+    }
+}
+
+/// Compare values of the same kind; values of
+/// different kinds are only ever unequal.
+fn compare(a: &str, b: &str, op: Op) -> bool {
+    let (a, b) = (Value::parse(a), Value::parse(b));
+    if std::mem::discriminant(&a) == std::mem::discriminant(&b) {
+        op.test(a.compare(&b))
+    } else {
+        op == Op::Ne
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '&' if chars.next_if_eq(&'&').is_some() => Token::And,
+            '|' if chars.next_if_eq(&'|').is_some() => Token::Or,
+            '=' if chars.next_if_eq(&'=').is_some() => Token::Op(Op::Eq),
+            '!' if chars.next_if_eq(&'=').is_some() => Token::Op(Op::Ne),
+            '!' if chars.next_if_eq(&'~').is_some() => Token::Op(Op::NotMatch),
+            '!' => Token::Not,
+            '~' => Token::Op(Op::Match),
+            '<' if chars.next_if_eq(&'=').is_some() => Token::Op(Op::Le),
+            '<' => Token::Op(Op::Lt),
+            '>' if chars.next_if_eq(&'=').is_some() => Token::Op(Op::Ge),
+            '>' => Token::Op(Op::Gt),
+            '"' | '\'' | '`' | '/' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some(end) if end == c => break,
+                        Some('\\') => match chars.next() {
+                            // Escapes are kept in regexes, bar that for the delimiter.
+                            Some(escaped) if c == '/' && escaped != '/' => {
+                                text.push('\\');
+                                text.push(escaped);
+                            }
+                            Some(escaped) => text.push(escaped),
+                            None => return Err(eyre!("Unterminated {}", c)),
+                        },
+                        Some(c) => text.push(c),
+                        None => return Err(eyre!("Unterminated {}", c)),
+                    }
+                }
+                match c {
+                    '`' => Token::Name(text),
+                    '/' => Token::Regex(text, chars.next_if_eq(&'i').is_some()),
+                    _ => Token::Text(text),
+                }
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let mut number = String::from(c);
+
+                // Signs are only taken as part of an exponent, as in `1e-3`.
+                while let Some(c) = chars.next_if(|c| {
+                    c.is_ascii_alphanumeric()
+                        || *c == '.'
+                        || (matches!(c, '-' | '+') && number.ends_with(['e', 'E']))
+                }) {
+                    number.push(c);
+                }
+                let value = number
+                    .parse()
+                    .map_err(|_| eyre!("Invalid number `{}`", number))?;
+                Token::Number(value)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut name = String::from(c);
+                while let Some(c) =
+                    chars.next_if(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | '-'))
+                {
+                    name.push(c);
+                }
+                Token::Name(name)
+            }
+            c => return Err(eyre!("Unexpected `{}`", c)),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Name(name) => format!("`{}`", name),
+        Token::Text(text) => format!("\"{}\"", text),
+        Token::Number(number) => format!("`{}`", number),
+        Token::Regex(regex, _) => format!("/{}/", regex),
+        Token::Op(op) => format!("`{}`", op.symbol()),
+        Token::And => "`&&`".into(),
+        Token::Or => "`||`".into(),
+        Token::Not => "`!`".into(),
+        Token::Open => "`(`".into(),
+        Token::Close => "`)`".into(),
+    }
+}
+
+/// A recursive descent parser, where `||` binds loosest,
+/// then `&&`, then `!`, then comparisons.
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    headers: &'a StringRecord,
+}
+impl Parser<'_> {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self.tokens.get(self.pos) == Some(token);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.eat(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.not()?;
+        while self.eat(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr> {
+        if self.eat(&Token::Not) {
+            Ok(Expr::Not(Box::new(self.not()?)))
+        } else if self.eat(&Token::Open) {
+            let expr = self.or()?;
+            if !self.eat(&Token::Close) {
+                return Err(eyre!("Missing `)`"));
+            }
+            Ok(expr)
+        } else {
+            self.compare()
+        }
+    }
+
+    fn compare(&mut self) -> Result<Expr> {
+        let lhs = self.operand()?;
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            Some(token) => return Err(eyre!("Expected a comparison, found {}", describe(&token))),
+            None => return Err(eyre!("Expected a comparison")),
+        };
+        let rhs = self.operand()?;
+
+        // Columns go on the left, so e.g. `250 < latency`
+        // is read as `latency > 250`.
+        let (col, op, rhs) = match (lhs, rhs) {
+            (Operand::Column(col), rhs) => (col, op, rhs),
+            (lhs, Operand::Column(col)) if !matches!(op, Op::Match | Op::NotMatch) => {
+                (col, op.flip(), lhs)
+            }
+            _ => return Err(eyre!("`{}` needs a column on the left", op.symbol())),
+        };
+        let rhs = match (op, rhs) {
+            (Op::Match | Op::NotMatch, Operand::Text(text)) => Operand::Regex(regex(&text, false)?),
+            (Op::Match | Op::NotMatch, Operand::Regex(regex)) => Operand::Regex(regex),
+            (Op::Match | Op::NotMatch, _) => {
+                return Err(eyre!("`{}` needs a /regex/ on the right", op.symbol()))
+            }
+            (_, Operand::Regex(_)) => {
+                return Err(eyre!("A /regex/ can only be used with `~` or `!~`"))
+            }
+            (_, rhs) => rhs,
+        };
+        Ok(Expr::Compare { col, op, rhs })
+    }
+
+    fn operand(&mut self) -> Result<Operand> {
+        match self.next() {
+            Some(Token::Name(name)) => self.column(&name).map(Operand::Column),
+            Some(Token::Text(text)) => Ok(Operand::Text(text)),
+            Some(Token::Number(number)) => Ok(Operand::Number(number)),
+            Some(Token::Regex(pattern, ignore_case)) => {
+                regex(&pattern, ignore_case).map(Operand::Regex)
+            }
+            Some(token) => Err(eyre!(
+                "Expected a column or value, found {}",
+                describe(&token)
+            )),
+            None => Err(eyre!("Expected a column or value")),
+        }
+    }
+
+    /// Find a column by name, falling back
+    /// to ignoring case if there's no exact match.
+    fn column(&self, name: &str) -> Result<usize> {
+        self.headers
+            .iter()
+            .position(|header| header == name)
+            .or_else(|| {
+                let mut matches = self
+                    .headers
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, header)| header.eq_ignore_ascii_case(name));
+                match (matches.next(), matches.next()) {
+                    (Some((idx, _)), None) => Some(idx),
+                    _ => None,
+                }
+            })
+            .ok_or_else(|| eyre!("No column named `{}`", name))
+    }
+}
+
+fn regex(pattern: &str, ignore_case: bool) -> Result<Regex> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|err| eyre!("Invalid regex: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::Rows;
+
+    fn records() -> Records {
+        let rows = [
+            ["db-1", "250", "2024-03-01", "error"],
+            ["web-2", "9", "2024-01-15", "ok"],
+            ["db-2", "", "2023-12-31", "ok"],
+            ["DB-3", "1000", "2024-02-01 10:00", "error"],
+        ];
+        Records {
+            headers: StringRecord::from(vec!["host", "latency", "when", "Status Code"]),
+            types: None,
+            columns: vec![],
+            rows: Rows::Loaded(
+                rows.iter()
+                    .map(|row| StringRecord::from(&row[..]))
+                    .collect(),
+            ),
+            errors: vec![],
+        }
+    }
+
+    /// The hosts of the rows matching the expression.
+    fn hosts(expr: &str) -> Vec<String> {
+        let records = records();
+        let filter = Filter::parse(expr, &records.headers).unwrap();
+        filter.check(&records).unwrap();
+        records
+            .window(0..records.len())
+            .iter()
+            .filter(|row| filter.matches(row))
+            .map(|row| row[0].to_string())
+            .collect()
+    }
+
+    fn error(expr: &str) -> String {
+        let records = records();
+        Filter::parse(expr, &records.headers)
+            .and_then(|filter| filter.check(&records))
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn compares_numbers_by_value() {
+        assert_eq!(hosts("latency > 100"), ["db-1", "DB-3"]);
+        assert_eq!(hosts("latency <= 9.0"), ["web-2"]);
+        assert_eq!(hosts("latency != 250"), ["web-2", "db-2", "DB-3"]);
+        assert_eq!(hosts("latency > 1e-3"), ["db-1", "web-2", "DB-3"]);
+        assert_eq!(hosts("latency < 1E+3"), ["db-1", "web-2"]);
+    }
+
+    #[test]
+    fn compares_dates_by_value() {
+        assert_eq!(hosts("when >= '2024-02-01'"), ["db-1", "DB-3"]);
+        assert_eq!(hosts("'2024-01-01' > when"), ["db-2"]);
+    }
+
+    #[test]
+    fn matches_text_exactly() {
+        assert_eq!(hosts("host == \"db-1\""), ["db-1"]);
+        assert_eq!(hosts("latency == '250.0'"), Vec::<String>::new());
+        assert_eq!(hosts("latency == ''"), ["db-2"]);
+    }
+
+    #[test]
+    fn matches_regexes() {
+        assert_eq!(hosts("host ~ /^db-/"), ["db-1", "db-2"]);
+        assert_eq!(hosts("host ~ /^db-/i"), ["db-1", "db-2", "DB-3"]);
+        assert_eq!(hosts("host !~ /\\d$/"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn combines_conditions() {
+        assert_eq!(
+            hosts("`Status Code` == 'error' && latency > 500 || host == 'web-2'"),
+            ["web-2", "DB-3"]
+        );
+        assert_eq!(hosts("!(host ~ /db/ || latency < 10)"), ["DB-3"]);
+    }
+
+    #[test]
+    fn finds_columns_ignoring_case() {
+        assert_eq!(hosts("HOST == 'web-2'"), ["web-2"]);
+        assert_eq!(hosts("`status code` == 'ok'"), ["web-2", "db-2"]);
+    }
+
+    #[test]
+    fn reports_invalid_expressions() {
+        assert_eq!(error("hots == 1"), "No column named `hots`");
+        assert_eq!(error("host == 'db"), "Unterminated '");
+        assert_eq!(error("host == 1 latency"), "Unexpected `latency`");
+        assert_eq!(error("/db/ ~ host"), "`~` needs a column on the left");
+        assert_eq!(error("host > 3"), "`host` has no numbers to compare with 3");
+    }
+}
@@ -0,0 +1,404 @@
+use std::{
+    fmt,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{eyre::eyre, Result};
+use csv::StringRecord;
+
+use crate::file::{
+    open, FileInfo, LoadError, LoadOptions, Progress, Records, RowError, Rows, Sheets, Source,
+    PROGRESS_INTERVAL,
+};
+
+/// How many lines to look at when inferring column boundaries.
+const SAMPLE_LINES: usize = 100;
+
+/// Tab stops are every this many characters.
+const TAB_WIDTH: usize = 8;
+
+/// How the columns of a fixed-width file were split.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixedWidth {
+    pub widths: Vec<usize>,
+    pub has_header: bool,
+}
+impl fmt::Display for FixedWidth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let widths: Vec<_> = self.widths.iter().map(|w| w.to_string()).collect();
+        write!(
+            f,
+            "widths {} {}",
+            widths.join(" "),
+            if self.has_header {
+                "header"
+            } else {
+                "no header"
+            }
+        )
+    }
+}
+
+/// Column widths and (optionally) names read from a spec file.
+struct Spec {
+    widths: Vec<usize>,
+    names: Option<StringRecord>,
+}
+
+/// Where the spec for a fixed-width file would be, e.g.
+/// `data.txt.widths` for `data.txt`.
+pub fn spec_path(path: &Path) -> PathBuf {
+    let mut spec = path.as_os_str().to_owned();
+    spec.push(".widths");
+    PathBuf::from(spec)
+}
+
+/// Read a spec with a column per line, each giving
+/// its width and then optionally its name, e.g. `10 id`.
+/// Blank lines and lines starting with `#` are ignored.
+fn read_spec(path: &Path) -> Result<Spec> {
+    let mut widths = vec![];
+    let mut names = vec![];
+    for (i, line) in fs_err::read_to_string(path)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (width, name) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let width = width.parse().map_err(|_| {
+            eyre!(
+                "Invalid width `{}` on line {} of {}",
+                width,
+                i + 1,
+                path.display()
+            )
+        })?;
+        widths.push(width);
+        names.push(name.trim().to_string());
+    }
+
+    // Names are only used if every column has one.
+    let names = names
+        .iter()
+        .all(|name| !name.is_empty())
+        .then(|| names.into_iter().collect());
+    Ok(Spec { widths, names })
+}
+
+/// Load a file where each column takes up a fixed number of
+/// characters. The widths are taken from the options, then a
+/// spec file next to the file, and otherwise inferred from how
+/// the text lines up.
+pub fn load_fixed_width(
+    source: &Source,
+    options: &LoadOptions,
+    info: &mut FileInfo,
+    progress: &Progress,
+) -> Result<Sheets> {
+    let spec = match (&options.widths, source.path().map(spec_path)) {
+        (Some(widths), _) => Some(Spec {
+            widths: widths.clone(),
+            names: None,
+        }),
+        (None, Some(path)) if path.exists() => Some(read_spec(&path)?),
+        _ => None,
+    };
+
+    let reader = BufReader::new(open(source, options, info, progress)?);
+    let mut lines = vec![];
+    for (i, line) in reader.split(b'\n').enumerate() {
+        let mut line = line.map_err(|err| LoadError {
+            message: err.to_string(),
+            line: Some(i as u64 + 1),
+            byte: None,
+        })?;
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        if i % PROGRESS_INTERVAL == 0 {
+            progress.update(i)?;
+        }
+        lines.push(expand_tabs(&String::from_utf8_lossy(&line)));
+    }
+
+    // Drop trailing blank lines, e.g. from a final newline.
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+
+    // Fields in fixed-width exports often run right up
+    // against each other, so that's only a problem if the
+    // columns were inferred from gaps between them.
+    let inferred = spec.is_none();
+
+    // A spec that names the columns stands in for the header.
+    let named = spec.as_ref().is_some_and(|spec| spec.names.is_some());
+    let has_header = options.dialect.has_header.unwrap_or(!named);
+    let starts = match &spec {
+        Some(spec) => spec
+            .widths
+            .iter()
+            .scan(0, |start, width| {
+                let col = *start;
+                *start += width;
+                Some(col)
+            })
+            .collect(),
+        None => {
+            let sample: Vec<_> = lines
+                .iter()
+                .take(SAMPLE_LINES)
+                .map(String::as_str)
+                .collect();
+            infer_starts(&sample, has_header)
+        }
+    };
+
+    let mut rows = lines.iter().map(|line| split(line, &starts));
+    let headers = match spec.and_then(|spec| spec.names) {
+        Some(names) => {
+            if has_header {
+                rows.next();
+            }
+            names
+        }
+        None if has_header => rows.next().unwrap_or_default(),
+        None => (0..starts.len())
+            .map(|idx| options.dialect.column_names.name(idx))
+            .collect(),
+    };
+    let rows: Vec<_> = rows.collect();
+
+    let first_row = if has_header { 1 } else { 0 };
+    let errors = if inferred {
+        lines
+            .iter()
+            .skip(first_row)
+            .enumerate()
+            .filter_map(|(row, line)| {
+                check(line, &starts).map(|message| RowError {
+                    row,
+                    line: (row + first_row) as u64 + 1,
+                    message,
+                })
+            })
+            .collect()
+    } else {
+        vec![]
+    };
+
+    info.fixed_width = Some(FixedWidth {
+        widths: widths(&starts, &lines),
+        has_header,
+    });
+    Ok(vec![(
+        String::new(),
+        Records {
+            headers,
+            types: None,
+            columns: vec![],
+            rows: Rows::Loaded(rows),
+            errors,
+        },
+    )])
+}
+
+/// Find where each column starts, going by the character
+/// positions that are blank on every line. A column needs text
+/// in both the header (if there is one) and the rows; otherwise
+/// it's merged into the column before it. This keeps the likes
+/// of "Mounted on" and free-text last columns in one piece.
+fn infer_starts(lines: &[&str], has_header: bool) -> Vec<usize> {
+    let width = lines.iter().map(|line| line.chars().count()).max();
+    let mut used = vec![false; width.unwrap_or_default()];
+    for line in lines {
+        for (i, c) in line.chars().enumerate() {
+            if !c.is_whitespace() {
+                used[i] = true;
+            }
+        }
+    }
+
+    let starts: Vec<_> = (0..used.len())
+        .filter(|&i| used[i] && (i == 0 || !used[i - 1]))
+        .collect();
+
+    let (header, data) = match lines.split_first() {
+        Some((header, data)) if has_header && !data.is_empty() => (Some(header), data),
+        _ => (None, lines),
+    };
+    let mut merged = vec![0];
+    for (i, &start) in starts.iter().enumerate().skip(1) {
+        let end = starts.get(i + 1).copied().unwrap_or(usize::MAX);
+        let has_text = |line: &&str| {
+            line.chars()
+                .skip(start)
+                .take(end - start)
+                .any(|c| !c.is_whitespace())
+        };
+        if data.iter().any(has_text) && header.is_none_or(has_text) {
+            merged.push(start);
+        }
+    }
+    merged
+}
+
+/// The widths of the columns, with the
+/// last one running to the end of the longest line.
+fn widths(starts: &[usize], lines: &[String]) -> Vec<usize> {
+    let end = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or_default();
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, start)| {
+            let next = starts.get(i + 1).copied().unwrap_or(end.max(*start));
+            next - start
+        })
+        .collect()
+}
+
+/// Cut a line into fields at the given starts,
+/// with the last field taking the rest of the line.
+fn split(line: &str, starts: &[usize]) -> StringRecord {
+    let chars: Vec<_> = line.chars().collect();
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| {
+            let start = start.min(chars.len());
+            let end = match starts.get(i + 1) {
+                Some(&end) => end.min(chars.len()),
+                None => chars.len(),
+            };
+            chars[start..end]
+                .iter()
+                .collect::<String>()
+                .trim()
+                .to_string()
+        })
+        .collect()
+}
+
+/// A field that spills over into the next one
+/// suggests the line doesn't fit the columns.
+fn check(line: &str, starts: &[usize]) -> Option<String> {
+    let chars: Vec<_> = line.chars().collect();
+    starts.iter().skip(1).enumerate().find_map(|(i, &start)| {
+        let before = chars.get(start.wrapping_sub(1))?;
+        let after = chars.get(start)?;
+        (!before.is_whitespace() && !after.is_whitespace())
+            .then(|| format!("field {} runs into field {}", i + 1, i + 2))
+    })
+}
+
+fn expand_tabs(line: &str) -> String {
+    if !line.contains('\t') {
+        return line.to_string();
+    }
+    let mut expanded = String::with_capacity(line.len());
+    let mut col = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let n = TAB_WIDTH - col % TAB_WIDTH;
+            expanded.extend(std::iter::repeat_n(' ', n));
+            col += n;
+        } else {
+            expanded.push(c);
+            col += 1;
+        }
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns(text: &str, has_header: bool) -> Vec<StringRecord> {
+        let lines: Vec<_> = text.lines().collect();
+        let starts = infer_starts(&lines, has_header);
+        lines.iter().map(|line| split(line, &starts)).collect()
+    }
+
+    #[test]
+    fn infers_columns_from_blank_positions() {
+        let text = "\
+id  name   score
+1   ann    9.5
+22  bob    10
+";
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(infer_starts(&lines, true), [0, 4, 11]);
+        assert_eq!(
+            columns(text, true),
+            [
+                StringRecord::from(vec!["id", "name", "score"]),
+                StringRecord::from(vec!["1", "ann", "9.5"]),
+                StringRecord::from(vec!["22", "bob", "10"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_headers_with_spaces_together() {
+        let text = "\
+Filesystem  Size  Mounted on
+/dev/sda1   50G   /
+tmpfs       1G    /run/user
+";
+        assert_eq!(
+            columns(text, true)[0],
+            StringRecord::from(vec!["Filesystem", "Size", "Mounted on"])
+        );
+    }
+
+    #[test]
+    fn keeps_free_text_together() {
+        let text = "\
+id level message
+1  ok    all good
+2  warn  disk is nearly full
+";
+        assert_eq!(
+            columns(text, true)[2],
+            StringRecord::from(vec!["2", "warn", "disk is nearly full"])
+        );
+    }
+
+    #[test]
+    fn splits_short_lines() {
+        assert_eq!(
+            split("ab", &[0, 4, 8]),
+            StringRecord::from(vec!["ab", "", ""])
+        );
+    }
+
+    #[test]
+    fn runs_the_last_column_to_the_longest_line() {
+        let lines = ["a   bb".to_string(), "c   dddd".to_string()];
+        assert_eq!(widths(&[0, 4], &lines), [4, 4]);
+    }
+
+    #[test]
+    fn flags_fields_running_into_the_next() {
+        assert_eq!(check("1   ann", &[0, 4]), None);
+        assert_eq!(
+            check("1234ann", &[0, 4]),
+            Some("field 1 runs into field 2".to_string())
+        );
+    }
+
+    #[test]
+    fn expands_tabs_to_tab_stops() {
+        assert_eq!(expand_tabs("a\tb"), format!("a{}b", " ".repeat(7)));
+        assert_eq!(
+            expand_tabs("abcdefgh\tb"),
+            format!("abcdefgh{}b", " ".repeat(8))
+        );
+    }
+}
@@ -0,0 +1,263 @@
+use std::{
+    fs::File,
+    io::{Seek, SeekFrom},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    thread,
+};
+
+use color_eyre::Result;
+use csv::{ByteRecord, StringRecord};
+
+use crate::{
+    dialect::Dialect,
+    file::{read_headers, RowError, RowWindow},
+};
+
+/// How many row offsets the indexer collects
+/// before publishing them to the shared index.
+const BATCH_SIZE: usize = 10_000;
+
+/// A byte-offset index of where each row in a CSV file starts.
+///
+/// The index is built on a background thread so the file
+/// can be displayed immediately; rows are then read from disk
+/// only when they're actually needed.
+#[derive(Debug)]
+pub struct RowIndex {
+    path: PathBuf,
+    dialect: Dialect,
+    offsets: Arc<RwLock<Vec<u64>>>,
+    errors: Arc<RwLock<Vec<RowError>>>,
+    done: Arc<AtomicBool>,
+    cancel: Arc<AtomicBool>,
+    window: RowWindow,
+}
+impl RowIndex {
+    /// Read the headers of the file and start indexing its rows.
+    pub fn build(path: &Path, dialect: &Dialect) -> Result<(StringRecord, Self)> {
+        let mut rdr = dialect.reader_builder().from_path(path)?;
+        let headers = read_headers(&mut rdr, dialect)?;
+        let n_fields = Some(headers.len()).filter(|_| !dialect.flexible);
+
+        let index = RowIndex {
+            path: path.to_path_buf(),
+            dialect: *dialect,
+            offsets: Arc::default(),
+            errors: Arc::default(),
+            done: Arc::default(),
+            cancel: Arc::default(),
+            window: RowWindow::default(),
+        };
+
+        let offsets = index.offsets.clone();
+        let errors = index.errors.clone();
+        let done = index.done.clone();
+        let cancel = index.cancel.clone();
+        thread::spawn(move || {
+            index_rows(rdr, n_fields, &offsets, &errors, &cancel);
+            done.store(true, Ordering::Relaxed);
+        });
+
+        Ok((headers, index))
+    }
+
+    /// The number of rows indexed so far.
+    pub fn len(&self) -> usize {
+        self.offsets.read().unwrap().len()
+    }
+
+    /// The malformed rows found so far.
+    pub fn errors(&self) -> Vec<RowError> {
+        self.errors.read().unwrap().clone()
+    }
+
+    pub fn n_errors(&self) -> usize {
+        self.errors.read().unwrap().len()
+    }
+
+    pub fn is_flagged(&self, idx: usize) -> bool {
+        self.errors
+            .read()
+            .unwrap()
+            .binary_search_by_key(&idx, |err| err.row)
+            .is_ok()
+    }
+
+    /// Whether the whole file has been indexed.
+    pub fn is_complete(&self) -> bool {
+        self.done.load(Ordering::Relaxed)
+    }
+
+    /// Get the rows in the provided range,
+    /// reading them from disk if they aren't cached.
+    pub fn rows(&self, range: Range<usize>) -> Vec<StringRecord> {
+        self.window.rows(range, self.len(), |start, count| {
+            self.read_rows(start, count)
+        })
+    }
+
+    /// Why rows last couldn't be read from disk, if they couldn't.
+    pub fn read_error(&self) -> Option<String> {
+        self.window.error()
+    }
+
+    /// Read up to the first `count` rows from the start of the
+    /// file, without waiting for them to be indexed.
+    pub fn head(&self, count: usize) -> Result<Vec<StringRecord>> {
+        let mut rdr = self.dialect.reader_builder().from_path(&self.path)?;
+        read_headers(&mut rdr, &self.dialect)?;
+        let mut rows = Vec::with_capacity(count);
+        let mut record = ByteRecord::new();
+        while rows.len() < count && rdr.read_byte_record(&mut record)? {
+            rows.push(StringRecord::from_byte_record_lossy(record.clone()));
+        }
+        Ok(rows)
+    }
+
+    pub fn read_rows(&self, start: usize, count: usize) -> Result<Vec<StringRecord>> {
+        let offset = match self.offsets.read().unwrap().get(start) {
+            Some(offset) => *offset,
+            None => return Ok(vec![]),
+        };
+
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut rdr = self
+            .dialect
+            .reader_builder()
+            .has_headers(false)
+            .from_reader(file);
+
+        let mut rows = Vec::with_capacity(count);
+        let mut record = ByteRecord::new();
+        while rows.len() < count && rdr.read_byte_record(&mut record)? {
+            rows.push(StringRecord::from_byte_record_lossy(record.clone()));
+        }
+        Ok(rows)
+    }
+}
+impl Drop for RowIndex {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+fn index_rows(
+    mut rdr: csv::Reader<File>,
+    n_fields: Option<usize>,
+    offsets: &RwLock<Vec<u64>>,
+    errors: &RwLock<Vec<RowError>>,
+    cancel: &AtomicBool,
+) {
+    let mut record = ByteRecord::new();
+    let mut n_rows = 0;
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    while !cancel.load(Ordering::Relaxed) {
+        match rdr.read_byte_record(&mut record) {
+            Ok(true) => {
+                if let Some(pos) = record.position() {
+                    batch.push(pos.byte());
+                }
+                if let Some(err) = RowError::check(&record, n_fields, n_rows) {
+                    errors.write().unwrap().push(err);
+                }
+                n_rows += 1;
+                if batch.len() >= BATCH_SIZE {
+                    offsets.write().unwrap().append(&mut batch);
+                }
+            }
+            Ok(false) => break,
+            // Reading stops at the first I/O error, which is flagged
+            // after the last row; whatever was indexed up to there
+            // stays viewable.
+            Err(err) => {
+                errors.write().unwrap().push(RowError {
+                    row: n_rows,
+                    line: err.position().map(|pos| pos.line()).unwrap_or_default(),
+                    message: format!("stopped reading: {}", err),
+                });
+                break;
+            }
+        }
+    }
+    offsets.write().unwrap().append(&mut batch);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn write_csv(name: &str, text: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("tabv-{}-{}.csv", name, std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    /// Index the file and wait for indexing to finish.
+    fn index(path: &Path) -> (StringRecord, RowIndex) {
+        let (headers, index) = RowIndex::build(path, &Dialect::default()).unwrap();
+        while !index.is_complete() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        (headers, index)
+    }
+
+    fn rows(rows: &[&[&str]]) -> Vec<StringRecord> {
+        rows.iter()
+            .map(|row| StringRecord::from(row.to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn indexes_where_each_row_starts() {
+        let path = write_csv("offsets", "id,name\n1,ann\n22,\"b\nob\"\n3,cat\n");
+        let (headers, index) = index(&path);
+
+        assert_eq!(headers, StringRecord::from(vec!["id", "name"]));
+        assert_eq!(index.len(), 3);
+        assert_eq!(*index.offsets.read().unwrap(), [8, 14, 24]);
+        assert_eq!(index.rows(1..3), rows(&[&["22", "b\nob"], &["3", "cat"]]));
+        assert_eq!(index.read_rows(2, 10).unwrap(), rows(&[&["3", "cat"]]));
+        assert!(index.read_rows(3, 10).unwrap().is_empty());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn flags_malformed_rows() {
+        let path = write_csv("malformed", "a,b\n1,2\n3\n4,5,6\n");
+        let (_, index) = index(&path);
+
+        assert_eq!(index.len(), 3);
+        let flagged: Vec<_> = index.errors().iter().map(|err| err.row).collect();
+        assert_eq!(flagged, [1, 2]);
+        assert!(!index.is_flagged(0));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn indexes_a_truncated_last_row() {
+        let path = write_csv("truncated", "a,b\n1,2\n3,\"fo");
+        let (_, index) = index(&path);
+
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.rows(0..2), rows(&[&["1", "2"], &["3", "fo"]]));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reports_rows_that_cant_be_read() {
+        let path = write_csv("removed", "a,b\n1,2\n3,4\n");
+        let (_, index) = index(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(index.rows(0..2).is_empty());
+        assert!(index.read_error().is_some());
+    }
+}
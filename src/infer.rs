@@ -0,0 +1,218 @@
+use std::{borrow::Cow, fmt};
+
+use csv::StringRecord;
+
+use crate::value::{parse_date, Value};
+
+/// The kind of values a column holds, going by what they look like.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    /// Every value is empty.
+    #[default]
+    Empty,
+    Int,
+    Float,
+    Bool,
+    Date,
+    DateTime,
+    Text,
+}
+impl ColumnType {
+    fn of(cell: &str) -> Self {
+        let trimmed = cell.trim();
+        match Value::parse(cell) {
+            Value::Null => ColumnType::Empty,
+            Value::Number(_) => {
+                let digits = trimmed.strip_prefix(['-', '+']).unwrap_or(trimmed);
+                if digits.bytes().all(|b| b.is_ascii_digit()) {
+                    ColumnType::Int
+                } else {
+                    ColumnType::Float
+                }
+            }
+            Value::DateTime(_) if parse_date(trimmed).is_some() => ColumnType::Date,
+            Value::DateTime(_) => ColumnType::DateTime,
+            Value::Text(_)
+                if trimmed.eq_ignore_ascii_case("true")
+                    || trimmed.eq_ignore_ascii_case("false") =>
+            {
+                ColumnType::Bool
+            }
+            Value::Text(_) => ColumnType::Text,
+        }
+    }
+
+    /// The type that covers values of both types.
+    fn merge(self, other: Self) -> Self {
+        use ColumnType::*;
+        match (self, other) {
+            (Empty, ty) | (ty, Empty) => ty,
+            (a, b) if a == b => a,
+            (Int, Float) | (Float, Int) => Float,
+            (Date, DateTime) | (DateTime, Date) => DateTime,
+            _ => Text,
+        }
+    }
+
+    pub fn is_numeric(self) -> bool {
+        matches!(self, ColumnType::Int | ColumnType::Float)
+    }
+}
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColumnType::Empty => "empty",
+            ColumnType::Int => "int",
+            ColumnType::Float => "float",
+            ColumnType::Bool => "bool",
+            ColumnType::Date => "date",
+            ColumnType::DateTime => "datetime",
+            ColumnType::Text => "text",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// What was inferred about a column from its values.
+#[derive(Debug, Default, Clone)]
+pub struct ColumnInfo {
+    pub ty: ColumnType,
+
+    /// How many of the rows looked at were empty,
+    /// and how many rows were looked at.
+    pub nulls: usize,
+    pub rows: usize,
+
+    // The most digits after the decimal point, for lining up
+    // the points in a column of floats.
+    decimals: usize,
+}
+impl ColumnInfo {
+    /// The cell's text, padded on the right so that its decimal
+    /// point lines up with the others' when right-aligned.
+    pub fn align<'a>(&self, cell: &'a str) -> Cow<'a, str> {
+        let trimmed = cell.trim();
+        if self.ty != ColumnType::Float || trimmed.is_empty() || is_scientific(trimmed) {
+            return Cow::Borrowed(cell);
+        }
+        let padding = match trimmed.split_once('.') {
+            Some((_, fraction)) => self.decimals.saturating_sub(fraction.len()),
+
+            // Account for the missing point as well.
+            None if self.decimals > 0 => self.decimals + 1,
+            None => return Cow::Borrowed(cell),
+        };
+        Cow::Owned(format!("{}{}", cell.trim_end(), " ".repeat(padding)))
+    }
+}
+
+/// Infer the type of each column from the provided rows.
+pub fn infer_columns(n_cols: usize, rows: &[StringRecord]) -> Vec<ColumnInfo> {
+    let mut columns = vec![
+        ColumnInfo {
+            rows: rows.len(),
+            ..Default::default()
+        };
+        n_cols
+    ];
+    for row in rows {
+        for (col, cell) in row.iter().enumerate().take(n_cols) {
+            let column = &mut columns[col];
+            let ty = ColumnType::of(cell);
+            match ty {
+                ColumnType::Empty => column.nulls += 1,
+                ColumnType::Float if !is_scientific(cell) => {
+                    if let Some((_, fraction)) = cell.trim().split_once('.') {
+                        column.decimals = column.decimals.max(fraction.len());
+                    }
+                }
+                _ => (),
+            }
+            column.ty = column.ty.merge(ty);
+        }
+
+        // Short rows are missing values at the end.
+        for column in columns.iter_mut().skip(row.len()) {
+            column.nulls += 1;
+        }
+    }
+    columns
+}
+
+/// Numbers in scientific notation can't be lined up by their points.
+fn is_scientific(number: &str) -> bool {
+    number.contains(['e', 'E'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn infer(rows: &[&[&str]]) -> Vec<ColumnInfo> {
+        let rows: Vec<_> = rows
+            .iter()
+            .map(|row| StringRecord::from(&row[..]))
+            .collect();
+        infer_columns(rows.first().map(|row| row.len()).unwrap_or_default(), &rows)
+    }
+
+    fn types(rows: &[&[&str]]) -> Vec<ColumnType> {
+        infer(rows).iter().map(|column| column.ty).collect()
+    }
+
+    #[test]
+    fn infers_types_from_values() {
+        use ColumnType::*;
+        assert_eq!(
+            types(&[&[
+                "1",
+                "1.5",
+                "true",
+                "2024-01-31",
+                "2024-01-31 12:00",
+                "a",
+                ""
+            ]]),
+            [Int, Float, Bool, Date, DateTime, Text, Empty]
+        );
+        assert_eq!(
+            types(&[&["-3", "+.5", "FALSE", "2024/01/31"]]),
+            [Int, Float, Bool, Date]
+        );
+    }
+
+    #[test]
+    fn merges_types_down_the_column() {
+        use ColumnType::*;
+        let rows: &[&[&str]] = &[
+            &["1", "2024-01-31", "1", "1", "2024-01-31", "1"],
+            &["2.5", "2024-02-01 10:00", "", "true", "x", "2024-01-31"],
+            &["3", "2024-02-02", "2", "0", "2024-02-01", "1.5"],
+        ];
+        assert_eq!(types(rows), [Float, DateTime, Int, Text, Text, Text]);
+    }
+
+    #[test]
+    fn counts_empty_and_missing_values_as_nulls() {
+        let columns = infer(&[&["1", "", "a"], &["2", " "], &["", "x", "b"]]);
+        let nulls: Vec<_> = columns.iter().map(|column| column.nulls).collect();
+        assert_eq!(nulls, [1, 2, 1]);
+        assert!(columns.iter().all(|column| column.rows == 3));
+    }
+
+    #[test]
+    fn pads_floats_to_line_up_their_points() {
+        let columns = infer(&[&["1.5"], &["10.25"], &["3"], &["1e-3"]]);
+        let column = &columns[0];
+        assert_eq!(column.ty, ColumnType::Float);
+        assert_eq!(column.align("1.5"), "1.5 ");
+        assert_eq!(column.align("10.25"), "10.25");
+        assert_eq!(column.align("3"), "3   ");
+        assert_eq!(column.align("1e-3"), "1e-3");
+        assert_eq!(column.align(""), "");
+
+        // Only columns of floats are padded.
+        let columns = infer(&[&["1"], &["10"]]);
+        assert_eq!(columns[0].align("1"), "1");
+    }
+}
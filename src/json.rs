@@ -0,0 +1,294 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader},
+};
+
+use color_eyre::Result;
+use csv::StringRecord;
+use serde_json::Value;
+
+use crate::file::{
+    open, FileInfo, LoadError, LoadOptions, Progress, Records, RowError, Rows, Sheets, Source,
+    PROGRESS_INTERVAL,
+};
+
+/// Name of the column for values that aren't objects. If the objects
+/// have a key by this name too, underscores are added to tell them apart.
+const VALUE_COLUMN: &str = "value";
+
+/// Load newline-delimited JSON, one value per line. Lines that
+/// aren't valid JSON are kept as empty rows and flagged.
+pub fn load_jsonl(
+    source: &Source,
+    options: &LoadOptions,
+    info: &mut FileInfo,
+    progress: &Progress,
+) -> Result<Sheets> {
+    let reader = BufReader::new(open(source, options, info, progress)?);
+    let mut table = Table::default();
+    let mut errors = vec![];
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(|err| LoadError {
+            message: err.to_string(),
+            line: Some(i as u64 + 1),
+            byte: None,
+        })?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if table.rows.len() % PROGRESS_INTERVAL == 0 {
+            progress.update(table.rows.len())?;
+        }
+        match serde_json::from_str(&line) {
+            Ok(value) => table.push(&value),
+            Err(err) => {
+                errors.push(RowError {
+                    row: table.rows.len(),
+                    line: i as u64 + 1,
+                    message: format!("invalid JSON: {}", err),
+                });
+                table.push(&Value::Object(Default::default()));
+            }
+        }
+    }
+    Ok(vec![(String::new(), table.into_records(errors))])
+}
+
+/// Load a JSON document, where each element of a top-level
+/// array is a row. Any other top-level value is a row of its own,
+/// so a stream of (possibly pretty-printed) objects works too.
+pub fn load_json(
+    source: &Source,
+    options: &LoadOptions,
+    info: &mut FileInfo,
+    progress: &Progress,
+) -> Result<Sheets> {
+    let reader = BufReader::new(open(source, options, info, progress)?);
+    let mut table = Table::default();
+    for value in serde_json::Deserializer::from_reader(reader).into_iter::<Value>() {
+        let value = value.map_err(|err| LoadError {
+            message: err.to_string(),
+            line: Some(err.line() as u64),
+            byte: None,
+        })?;
+        let values = match value {
+            Value::Array(values) => values,
+            value => vec![value],
+        };
+        for value in &values {
+            if table.rows.len() % PROGRESS_INTERVAL == 0 {
+                progress.update(table.rows.len())?;
+            }
+            table.push(value);
+        }
+    }
+    Ok(vec![(String::new(), table.into_records(vec![]))])
+}
+
+/// Rows of flattened values, with columns
+/// added as new keys turn up.
+#[derive(Default)]
+struct Table {
+    columns: Vec<String>,
+    indices: HashMap<String, usize>,
+    // Kept apart from the keys so that it doesn't clash with
+    // a key named `value`.
+    value_column: Option<usize>,
+
+    // Values as (column index, value).
+    rows: Vec<Vec<(usize, String)>>,
+}
+impl Table {
+    fn push(&mut self, value: &Value) {
+        let row = match value {
+            Value::Object(_) => {
+                let mut fields = vec![];
+                flatten("", value, &mut fields);
+                fields
+                    .into_iter()
+                    .map(|(key, val)| (self.column(key), val))
+                    .collect()
+            }
+            _ => vec![(self.value_column(), format_value(value))],
+        };
+        self.rows.push(row);
+    }
+
+    fn value_column(&mut self) -> usize {
+        *self.value_column.get_or_insert_with(|| {
+            self.columns.push(String::new());
+            self.columns.len() - 1
+        })
+    }
+
+    fn column(&mut self, key: String) -> usize {
+        if let Some(idx) = self.indices.get(&key) {
+            return *idx;
+        }
+        let idx = self.columns.len();
+        self.columns.push(key.clone());
+        self.indices.insert(key, idx);
+        idx
+    }
+
+    fn into_records(mut self, errors: Vec<RowError>) -> Records {
+        if let Some(idx) = self.value_column {
+            let mut name = VALUE_COLUMN.to_string();
+            while self.indices.contains_key(&name) {
+                name.push('_');
+            }
+            self.columns[idx] = name;
+        }
+        let n_cols = self.columns.len();
+        let rows = self
+            .rows
+            .into_iter()
+            .map(|fields| {
+                let mut row = vec![String::new(); n_cols];
+                for (idx, val) in fields {
+                    row[idx] = val;
+                }
+                StringRecord::from(row)
+            })
+            .collect();
+        Records {
+            headers: StringRecord::from(self.columns),
+            types: None,
+            columns: vec![],
+            rows: Rows::Loaded(rows),
+            errors,
+        }
+    }
+}
+
+/// Flatten nested objects into dotted keys, e.g. `{"a": {"b": 1}}`
+/// becomes `a.b`. Arrays and empty objects are kept whole as compact JSON.
+fn flatten(prefix: &str, value: &Value, fields: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(map) if map.is_empty() && !prefix.is_empty() => {
+            fields.push((prefix.to_string(), format_value(value)))
+        }
+        Value::Object(map) => {
+            for (key, value) in map {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(&key, value, fields);
+            }
+        }
+        _ => fields.push((prefix.to_string(), format_value(value))),
+    }
+}
+
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        _ => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn flattened(value: Value) -> Vec<(String, String)> {
+        let mut fields = vec![];
+        flatten("", &value, &mut fields);
+        fields
+    }
+
+    fn records(values: &[Value]) -> Records {
+        let mut table = Table::default();
+        for value in values {
+            table.push(value);
+        }
+        table.into_records(vec![])
+    }
+
+    #[test]
+    fn flattens_nested_objects_into_dotted_keys() {
+        let fields =
+            flattened(json!({"id": 1, "user": {"name": "ann", "address": {"city": "Oslo"}}}));
+        let expected = [
+            ("id", "1"),
+            ("user.name", "ann"),
+            ("user.address.city", "Oslo"),
+        ];
+        assert_eq!(
+            fields,
+            expected.map(|(key, val)| (key.to_string(), val.to_string()))
+        );
+    }
+
+    #[test]
+    fn keeps_arrays_whole() {
+        let fields = flattened(json!({"tags": ["a", "b"], "points": [{"x": 1}]}));
+        let expected = [("tags", r#"["a","b"]"#), ("points", r#"[{"x":1}]"#)];
+        assert_eq!(
+            fields,
+            expected.map(|(key, val)| (key.to_string(), val.to_string()))
+        );
+    }
+
+    #[test]
+    fn keeps_empty_objects() {
+        let fields = flattened(json!({"meta": {}, "ctx": {"tags": {}}}));
+        let expected = [("meta", "{}"), ("ctx.tags", "{}")];
+        assert_eq!(
+            fields,
+            expected.map(|(key, val)| (key.to_string(), val.to_string()))
+        );
+        assert_eq!(flattened(json!({})), []);
+    }
+
+    #[test]
+    fn formats_scalars() {
+        assert_eq!(format_value(&json!(null)), "");
+        assert_eq!(format_value(&json!("a \"b\"")), "a \"b\"");
+        assert_eq!(format_value(&json!(true)), "true");
+        assert_eq!(format_value(&json!(1.5)), "1.5");
+    }
+
+    #[test]
+    fn adds_columns_as_keys_turn_up() {
+        let records = records(&[
+            json!({"a": 1, "b": {"c": 2}}),
+            json!({"b": {"d": 3}, "a": 4}),
+            json!("plain"),
+        ]);
+        assert_eq!(
+            records.headers,
+            StringRecord::from(vec!["a", "b.c", "b.d", "value"])
+        );
+        assert_eq!(
+            records.window(0..3).into_owned(),
+            [
+                StringRecord::from(vec!["1", "2", "", ""]),
+                StringRecord::from(vec!["4", "", "3", ""]),
+                StringRecord::from(vec!["", "", "", "plain"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_values_apart_from_a_key_named_value() {
+        let records = records(&[json!(1), json!({"value": 2}), json!({"value_": 3})]);
+        assert_eq!(
+            records.headers,
+            StringRecord::from(vec!["value__", "value", "value_"])
+        );
+        assert_eq!(
+            records.window(0..3).into_owned(),
+            [
+                StringRecord::from(vec!["1", "", ""]),
+                StringRecord::from(vec!["", "2", ""]),
+                StringRecord::from(vec!["", "", "3"]),
+            ]
+        );
+    }
+}
@@ -1,6 +1,25 @@
 mod app;
+mod columnar;
+mod compression;
+mod dialect;
+mod encoding;
 mod file;
+mod filter;
+mod fixed_width;
+mod index;
+mod infer;
+mod json;
+mod markup;
+mod scan;
+mod search;
+mod sort;
+mod spreadsheet;
+mod sqlite;
+mod value;
 mod views;
+mod watch;
 
 pub use app::App;
-pub use file::TableFile;
+pub use dialect::{parse_byte, ColumnNames, DialectOptions};
+pub use encoding::parse_encoding;
+pub use file::{LoadOptions, TableFile};
@@ -1,38 +1,349 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::HashSet,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+};
 
 use clap::{Parser, ValueHint};
 use color_eyre::Result;
-use glob::glob;
-use tabv::{App, TableFile};
+use encoding_rs::Encoding;
+use glob::{glob, Pattern};
+use tabv::{parse_byte, parse_encoding, App, ColumnNames, DialectOptions, LoadOptions, TableFile};
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about)]
 struct Args {
+    /// Files, directories or glob patterns to view, or `-` to read
+    /// from stdin. Defaults to stdin if it's piped, otherwise the
+    /// current directory.
     #[clap(value_hint = ValueHint::FilePath)]
-    path: Option<PathBuf>,
+    paths: Vec<PathBuf>,
+
+    /// Only view files in directories that match this pattern,
+    /// e.g. `**/metrics.csv`. Can be given more than once.
+    #[clap(long, value_parser = Pattern::new)]
+    include: Vec<Pattern>,
+
+    /// Skip files in directories that match this pattern,
+    /// e.g. `**/tmp/**`, or in directories that match it,
+    /// e.g. `**/tmp`. Can be given more than once.
+    #[clap(long, value_parser = Pattern::new)]
+    exclude: Vec<Pattern>,
+
+    /// Field delimiter, e.g. `;` or `\t`.
+    /// Detected from the file if not given.
+    #[clap(short, long, value_parser = parse_byte)]
+    delimiter: Option<u8>,
+
+    /// Quote character. Detected from the file if not given.
+    #[clap(long, value_parser = parse_byte)]
+    quote: Option<u8>,
+
+    /// Escape character for quotes within quoted fields,
+    /// instead of doubling them.
+    #[clap(long, value_parser = parse_byte)]
+    escape: Option<u8>,
+
+    /// Lines starting with this character are ignored.
+    #[clap(long, value_parser = parse_byte)]
+    comment: Option<u8>,
+
+    /// Treat the first row as data rather than column names.
+    /// Otherwise whether there's a header is detected from the file.
+    #[clap(long)]
+    no_header: bool,
+
+    /// What to call the columns of files without a header.
+    #[clap(long, value_enum, default_value_t)]
+    column_names: ColumnNames,
+
+    /// Allow rows to have differing numbers of fields
+    /// without flagging them as malformed.
+    #[clap(long)]
+    flexible: bool,
+
+    /// Text encoding, e.g. `utf-16le` or `windows-1252`.
+    /// Detected from the file if not given.
+    #[clap(long, value_parser = parse_encoding)]
+    encoding: Option<&'static Encoding>,
+
+    /// Column widths for fixed-width files, e.g. `10,20,8`.
+    /// Otherwise read from a `<file>.widths` spec if there is one,
+    /// or inferred from how the text lines up.
+    #[clap(long, value_delimiter = ',')]
+    widths: Option<Vec<usize>>,
+}
+impl Args {
+    fn load_options(&self) -> LoadOptions {
+        LoadOptions {
+            dialect: DialectOptions {
+                delimiter: self.delimiter,
+                quote: self.quote,
+                escape: self.escape,
+                comment: self.comment,
+                has_header: self.no_header.then_some(false),
+                column_names: self.column_names,
+                flexible: self.flexible,
+            },
+            encoding: self.encoding,
+            widths: self.widths.clone(),
+        }
+    }
+
+    /// Whether a file found in `dir` passes the include and exclude
+    /// patterns. These are matched against both the file's path
+    /// and its path relative to `dir`. Files in excluded
+    /// directories are excluded as well.
+    fn is_included(&self, dir: &Path, path: &Path) -> bool {
+        let relative = path.strip_prefix(dir).unwrap_or(path);
+        let matches =
+            |pattern: &Pattern| pattern.matches_path(relative) || pattern.matches_path(path);
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !relative
+                .ancestors()
+                .any(|ancestor| self.is_excluded(dir, ancestor))
+    }
+
+    /// Whether a path relative to `dir` matches any of the exclude patterns.
+    /// Patterns that match `dir` itself, like `**/tmp/**` when looking
+    /// through a directory under `/tmp`, are only matched against the
+    /// relative path, as otherwise they'd exclude everything.
+    fn is_excluded(&self, dir: &Path, relative: &Path) -> bool {
+        !relative.as_os_str().is_empty()
+            && self.exclude.iter().any(|pattern| {
+                pattern.matches_path(relative)
+                    || (!pattern.matches_path(dir) && pattern.matches_path(&dir.join(relative)))
+            })
+    }
+}
+
+/// The supported files under `dir`, not going into
+/// directories that are excluded.
+fn find_csvs(dir: &Path, args: &Args) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut visited = HashSet::new();
+    walk(dir, dir, args, &mut visited, &mut files)?;
+    Ok(files)
+}
+
+/// Add the files in `current` and its subdirectories, in order.
+/// Directories are followed through symlinks, but only once.
+fn walk(
+    dir: &Path,
+    current: &Path,
+    args: &Args,
+    visited: &mut HashSet<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) -> Result<()> {
+    if !visited.insert(current.canonicalize()?) {
+        return Ok(());
+    }
+    let mut paths = fs_err::read_dir(current)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    paths.sort();
+    for path in paths {
+        let relative = path.strip_prefix(dir).unwrap_or(&path);
+        if path.is_dir() {
+            // Directories that can't be read are skipped.
+            if !args.is_excluded(dir, relative) {
+                let _ = walk(dir, &path, args, visited, files);
+            }
+        } else if TableFile::is_discoverable(&path) && args.is_included(dir, &path) {
+            files.push(path);
+        }
+    }
+    Ok(())
 }
 
-fn find_csvs(path: &Path) -> Vec<TableFile> {
-    let pattern = path.join("**/*.csv?").display().to_string();
-    glob(&pattern)
-        .expect("Failed to read glob pattern")
-        .filter_map(|path| path.ok().map(TableFile::new))
+/// The files a path refers to: the supported files under it if
+/// it's a directory, those it matches if it's a glob pattern,
+/// or otherwise the path itself. Directories that are searched
+/// are added to `dirs`.
+fn expand(path: &Path, args: &Args, dirs: &mut Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+    if path.is_dir() {
+        dirs.push(path.to_path_buf());
+        return find_csvs(path, args);
+    } else if path.exists() || path == Path::new("-") {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let matches: Vec<_> = glob(&path.display().to_string())
+        .map(|paths| paths.filter_map(|path| path.ok()).collect())
+        .unwrap_or_default();
+    if matches.is_empty() {
+        // Kept so that it's shown as missing.
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = vec![];
+    for path in matches {
+        if path.is_dir() {
+            files.extend(find_csvs(&path, args)?);
+            dirs.push(path);
+        } else if TableFile::is_supported(&path) {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// The paths with any that refer to a file already listed removed.
+/// The same file may be reached through different paths,
+/// e.g. both by name and through its directory.
+fn dedup(paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut seen = HashSet::new();
+    paths
+        .into_iter()
+        .filter(|path| seen.insert(path.canonicalize().unwrap_or_else(|_| path.clone())))
         .collect()
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
-    let path = args.path.unwrap_or_else(|| PathBuf::from("."));
-
-    let files = if path.is_dir() {
-        find_csvs(&path)
-    } else {
-        vec![TableFile::new(path)]
+    let options = args.load_options();
+    let paths = match args.paths.as_slice() {
+        [] if !io::stdin().is_terminal() => vec![PathBuf::from("-")],
+        [] => vec![PathBuf::from(".")],
+        paths => paths.to_vec(),
     };
 
+    let mut dirs = vec![];
+    let mut expanded = vec![];
+    for path in &paths {
+        expanded.extend(expand(path, &args, &mut dirs)?);
+    }
+    let files = dedup(expanded)
+        .into_iter()
+        .map(|path| {
+            // Keyboard input is read from the terminal rather than
+            // stdin, so the viewer still works with piped data.
+            if path == Path::new("-") {
+                TableFile::stdin(&options)
+            } else {
+                TableFile::new(path, &options)
+            }
+        })
+        .collect();
+
     color_eyre::install()?;
     let terminal = ratatui::init();
-    let app_result = App::new(files).run(terminal);
+    let app = App::new(files).watch(dirs, move |dir, path| {
+        args.is_included(dir, path)
+            .then(|| TableFile::new(path.to_path_buf(), &options))
+    });
+    let app_result = app.run(terminal);
     ratatui::restore();
     app_result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create the files under a new temporary directory.
+    fn tree(name: &str, files: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tabv-{}-{}", name, std::process::id()));
+        let _ = fs_err::remove_dir_all(&dir);
+        for file in files {
+            let path = dir.join(file);
+            fs_err::create_dir_all(path.parent().unwrap()).unwrap();
+            fs_err::write(path, "a,b\n1,2\n").unwrap();
+        }
+        dir
+    }
+
+    fn args(flags: &[&str]) -> Args {
+        Args::parse_from(["tabv"].iter().chain(flags))
+    }
+
+    /// The files found under `dir`, relative to it.
+    fn found(dir: &Path, args: &Args) -> Vec<String> {
+        find_csvs(dir, args)
+            .unwrap()
+            .iter()
+            .map(|path| path.strip_prefix(dir).unwrap().display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn finds_supported_files_in_order() {
+        let dir = tree("find", &["b.csv", "a/x.tsv", "a/notes.md", "readme.txt"]);
+        assert_eq!(found(&dir, &args(&[])), ["a/x.tsv", "b.csv"]);
+        fs_err::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn filters_files_with_include_and_exclude_patterns() {
+        let dir = tree(
+            "patterns",
+            &[
+                "runs/1/metrics.csv",
+                "runs/1/log.csv",
+                "runs/tmp/metrics.csv",
+                "top.csv",
+            ],
+        );
+        let include = args(&["--include", "**/metrics.csv"]);
+        assert_eq!(
+            found(&dir, &include),
+            ["runs/1/metrics.csv", "runs/tmp/metrics.csv"]
+        );
+        let exclude = args(&["--exclude", "**/tmp"]);
+        assert_eq!(
+            found(&dir, &exclude),
+            ["runs/1/log.csv", "runs/1/metrics.csv", "top.csv"]
+        );
+        let both = args(&["--include", "**/metrics.csv", "--exclude", "**/tmp/**"]);
+        assert_eq!(found(&dir, &both), ["runs/1/metrics.csv"]);
+        fs_err::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follows_symlinked_directories_once() {
+        let dir = tree("symlinks", &["a/x.csv"]);
+        std::os::unix::fs::symlink(&dir, dir.join("a/loop")).unwrap();
+        std::os::unix::fs::symlink(dir.join("a"), dir.join("b")).unwrap();
+        // `b` is the same directory as `a`, and `a/loop` leads back
+        // to the top, so each file is only found once.
+        assert_eq!(found(&dir, &args(&[])), ["a/x.csv"]);
+        fs_err::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn expands_directories_patterns_and_missing_paths() {
+        let dir = tree("expand", &["a/x.csv", "b/y.csv", "b/z.json"]);
+        let args = args(&[]);
+        let mut dirs = vec![];
+        let files = expand(&dir.join("a"), &args, &mut dirs).unwrap();
+        assert_eq!(files, [dir.join("a/x.csv")]);
+        assert_eq!(dirs, [dir.join("a")]);
+
+        let files = expand(&dir.join("*/*.csv"), &args, &mut dirs).unwrap();
+        assert_eq!(files, [dir.join("a/x.csv"), dir.join("b/y.csv")]);
+        let missing = dir.join("missing.csv");
+        assert_eq!(expand(&missing, &args, &mut dirs).unwrap(), [missing]);
+        fs_err::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn drops_paths_to_files_already_listed() {
+        let dir = tree("dedup", &["a/x.csv", "y.csv"]);
+        let paths = vec![
+            dir.join("a/x.csv"),
+            dir.join("y.csv"),
+            dir.join("a/../a/x.csv"),
+            dir.join("./y.csv"),
+            dir.join("missing.csv"),
+        ];
+        assert_eq!(
+            dedup(paths),
+            [
+                dir.join("a/x.csv"),
+                dir.join("y.csv"),
+                dir.join("missing.csv")
+            ]
+        );
+        fs_err::remove_dir_all(&dir).unwrap();
+    }
+}
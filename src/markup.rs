@@ -0,0 +1,361 @@
+use std::io::Read;
+
+use color_eyre::{eyre::eyre, Result};
+use csv::StringRecord;
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use scraper::{ElementRef, Html, Selector};
+
+use crate::file::{open, FileInfo, LoadOptions, Progress, Records, Rows, Sheets, Source};
+
+/// A table pulled out of a document, before it's
+/// squared up into records.
+struct Table {
+    name: Option<String>,
+    header: Option<Vec<String>>,
+    rows: Vec<Vec<String>>,
+}
+
+/// Load each table in a Markdown document as a sheet,
+/// named after the heading it comes under.
+pub fn load_markdown(
+    source: &Source,
+    options: &LoadOptions,
+    info: &mut FileInfo,
+    progress: &Progress,
+) -> Result<Sheets> {
+    let mut text = String::new();
+    open(source, options, info, progress)?.read_to_string(&mut text)?;
+
+    let mut tables = vec![];
+    let mut heading: Option<String> = None;
+
+    // The text of the heading or cell being read.
+    let mut buffer: Option<String> = None;
+    let mut row = vec![];
+    let mut table: Option<Table> = None;
+    for event in Parser::new_ext(&text, Options::ENABLE_TABLES) {
+        match event {
+            Event::Start(Tag::Heading { .. } | Tag::TableCell) => buffer = Some(String::new()),
+            Event::End(TagEnd::Heading(_)) => {
+                heading = buffer.take().map(|text| text.trim().to_string());
+            }
+            Event::End(TagEnd::TableCell) => {
+                row.push(buffer.take().unwrap_or_default().trim().to_string());
+            }
+            Event::Start(Tag::Table(_)) => {
+                table = Some(Table {
+                    name: heading.clone(),
+                    header: None,
+                    rows: vec![],
+                });
+            }
+            Event::End(TagEnd::TableHead) => {
+                if let Some(table) = &mut table {
+                    table.header = Some(std::mem::take(&mut row));
+                }
+            }
+            Event::End(TagEnd::TableRow) => {
+                if let Some(table) = &mut table {
+                    table.rows.push(std::mem::take(&mut row));
+                }
+            }
+            Event::End(TagEnd::Table) => tables.extend(table.take()),
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(buffer) = &mut buffer {
+                    buffer.push_str(&text);
+                }
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                if let Some(buffer) = &mut buffer {
+                    buffer.push(' ');
+                }
+            }
+            _ => (),
+        }
+    }
+    into_sheets(tables, options, progress)
+}
+
+/// Load each table in an HTML document as a sheet, named
+/// after its caption or otherwise the heading it comes under.
+pub fn load_html(
+    source: &Source,
+    options: &LoadOptions,
+    info: &mut FileInfo,
+    progress: &Progress,
+) -> Result<Sheets> {
+    let mut text = String::new();
+    open(source, options, info, progress)?.read_to_string(&mut text)?;
+    let document = Html::parse_document(&text);
+
+    // Elements are selected in document order, so the
+    // last heading seen is the one preceding the table.
+    let selector = Selector::parse("h1, h2, h3, h4, h5, h6, table").unwrap();
+    let mut tables = vec![];
+    let mut heading = None;
+    for elem in document.select(&selector) {
+        if elem.value().name() != "table" {
+            heading = Some(text_of(elem));
+            continue;
+        }
+
+        let caption = children(elem)
+            .find(|child| child.value().name() == "caption")
+            .map(text_of);
+
+        // Rows are either directly under the table or in its
+        // sections; those of any nested tables are left out.
+        let rows = children(elem).flat_map(|child| match child.value().name() {
+            "thead" | "tbody" | "tfoot" => children(child).collect(),
+            _ => vec![child],
+        });
+        let mut header = None;
+        let mut body = vec![];
+        for tr in rows.filter(|row| row.value().name() == "tr") {
+            // A row is the header if it's in the table's head
+            // or, failing that, if it's all header cells.
+            let in_head = tr
+                .parent()
+                .and_then(ElementRef::wrap)
+                .is_some_and(|parent| parent.value().name() == "thead");
+            let mut is_header = true;
+            let mut cells = vec![];
+            for cell in children(tr) {
+                let name = cell.value().name();
+                if name != "th" && name != "td" {
+                    continue;
+                }
+                is_header &= in_head || name == "th";
+                cells.push(text_of(cell));
+
+                // Spanned columns are left empty.
+                let span: usize = cell
+                    .attr("colspan")
+                    .and_then(|span| span.parse().ok())
+                    .unwrap_or(1);
+                cells.extend(std::iter::repeat_n(String::new(), span.saturating_sub(1)));
+            }
+            if header.is_none() && body.is_empty() && is_header && !cells.is_empty() {
+                header = Some(cells);
+            } else {
+                body.push(cells);
+            }
+        }
+
+        tables.push(Table {
+            name: caption.or_else(|| heading.clone()),
+            header,
+            rows: body,
+        });
+    }
+    into_sheets(tables, options, progress)
+}
+
+fn children(elem: ElementRef) -> impl Iterator<Item = ElementRef> {
+    elem.children().filter_map(ElementRef::wrap)
+}
+
+/// The element's text with whitespace collapsed,
+/// as a browser would show it.
+fn text_of(elem: ElementRef) -> String {
+    elem.text()
+        .flat_map(str::split_whitespace)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Turn the tables into sheets, padding out short rows.
+/// Tables without a name are numbered, and tables under
+/// the same heading are told apart by a count, e.g. `Usage (2)`.
+fn into_sheets(tables: Vec<Table>, options: &LoadOptions, progress: &Progress) -> Result<Sheets> {
+    if tables.is_empty() {
+        return Err(eyre!("No tables found"));
+    }
+
+    let mut sheets: Sheets = vec![];
+    let mut n_rows = 0;
+    for (i, table) in tables.into_iter().enumerate() {
+        let base = table
+            .name
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| format!("table {}", i + 1));
+        let mut name = base.clone();
+        let mut n = 1;
+        while sheets.iter().any(|(other, _)| *other == name) {
+            n += 1;
+            name = format!("{} ({})", base, n);
+        }
+
+        let mut rows = table.rows;
+        let header = match (table.header, options.dialect.has_header) {
+            (Some(header), Some(false)) => {
+                rows.insert(0, header);
+                None
+            }
+            (Some(header), _) => Some(header),
+            (None, Some(true)) if !rows.is_empty() => Some(rows.remove(0)),
+            (None, _) => None,
+        };
+
+        let width = header
+            .iter()
+            .chain(&rows)
+            .map(Vec::len)
+            .max()
+            .unwrap_or_default();
+        let mut header = header.unwrap_or_default();
+        for idx in header.len()..width {
+            header.push(options.dialect.column_names.name(idx));
+        }
+        let rows: Vec<_> = rows
+            .into_iter()
+            .map(|mut row| {
+                row.resize(width, String::new());
+                StringRecord::from(row)
+            })
+            .collect();
+
+        n_rows += rows.len();
+        progress.update(n_rows)?;
+        sheets.push((
+            name,
+            Records {
+                headers: StringRecord::from(header),
+                types: None,
+                columns: vec![],
+                rows: Rows::Loaded(rows),
+                errors: vec![],
+            },
+        ));
+    }
+    Ok(sheets)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dialect::DialectOptions;
+
+    use super::*;
+
+    fn load(name: &str, text: &str, has_header: Option<bool>) -> Sheets {
+        let path = std::env::temp_dir().join(format!("tabv-{}-{}", std::process::id(), name));
+        std::fs::write(&path, text).unwrap();
+        let options = LoadOptions {
+            dialect: DialectOptions {
+                has_header,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let source = Source::Path(path.clone());
+        let load = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("md") => load_markdown,
+            _ => load_html,
+        };
+        let sheets = load(
+            &source,
+            &options,
+            &mut FileInfo::default(),
+            &Progress::default(),
+        );
+        std::fs::remove_file(path).unwrap();
+        sheets.unwrap()
+    }
+
+    fn names(sheets: &Sheets) -> Vec<&str> {
+        sheets.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    fn rows(records: &Records) -> Vec<StringRecord> {
+        records.window(0..records.len()).into_owned()
+    }
+
+    #[test]
+    fn finds_html_headers_in_the_head_or_all_th_rows() {
+        let sheets = load(
+            "headers.html",
+            "<table><thead><tr><td>a</td><td>b</td></tr></thead>
+                <tbody><tr><th>1</th><td>2</td></tr></tbody></table>
+            <table><tr><th>c</th><th>d</th></tr><tr><td>3</td><td>4</td></tr></table>
+            <table><tr><th>e</th><td>f</td></tr><tr><td>5</td><td>6</td></tr></table>",
+            None,
+        );
+        let headers: Vec<_> = sheets.iter().map(|(_, recs)| &recs.headers).collect();
+        assert_eq!(
+            headers,
+            [
+                &StringRecord::from(vec!["a", "b"]),
+                &StringRecord::from(vec!["c", "d"]),
+                &StringRecord::from(vec!["A", "B"]),
+            ]
+        );
+        assert_eq!(rows(&sheets[0].1), [StringRecord::from(vec!["1", "2"])]);
+        assert_eq!(sheets[2].1.len(), 2);
+    }
+
+    #[test]
+    fn pads_out_spanned_columns_and_short_rows() {
+        let sheets = load(
+            "spans.html",
+            "<table><tr><th>a</th><th>b</th><th>c</th></tr>
+                <tr><td colspan=\"2\">1</td><td>2</td></tr>
+                <tr><td>3</td></tr></table>",
+            None,
+        );
+        assert_eq!(
+            rows(&sheets[0].1),
+            [
+                StringRecord::from(vec!["1", "", "2"]),
+                StringRecord::from(vec!["3", "", ""]),
+            ]
+        );
+    }
+
+    #[test]
+    fn names_html_tables_by_caption_then_heading() {
+        let table = "<tr><th>a</th></tr><tr><td>1</td></tr>";
+        let sheets = load(
+            "names.html",
+            &format!(
+                "<table>{table}</table>
+                <h2>Usage</h2>
+                <table><caption>Prices</caption>{table}</table>
+                <table>{table}</table>
+                <table>{table}</table>"
+            ),
+            None,
+        );
+        assert_eq!(names(&sheets), ["table 1", "Prices", "Usage", "Usage (2)"]);
+    }
+
+    #[test]
+    fn names_markdown_tables_by_heading() {
+        let sheets = load(
+            "names.md",
+            "| a | b |\n|---|---|\n| 1 | 2 |\n\n\
+            # Usage\n\n| a |\n|---|\n| 1 |\n\n| b |\n|---|\n| 2 |\n",
+            None,
+        );
+        assert_eq!(names(&sheets), ["table 1", "Usage", "Usage (2)"]);
+        assert_eq!(sheets[0].1.headers, StringRecord::from(vec!["a", "b"]));
+        assert_eq!(rows(&sheets[0].1), [StringRecord::from(vec!["1", "2"])]);
+    }
+
+    #[test]
+    fn keeps_the_header_as_a_row_without_headers() {
+        let sheets = load(
+            "no-header.md",
+            "| a | b |\n|---|---|\n| 1 | 2 |\n",
+            Some(false),
+        );
+        let (_, records) = &sheets[0];
+        assert_eq!(records.headers, StringRecord::from(vec!["A", "B"]));
+        assert_eq!(
+            rows(records),
+            [
+                StringRecord::from(vec!["a", "b"]),
+                StringRecord::from(vec!["1", "2"]),
+            ]
+        );
+    }
+}
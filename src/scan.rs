@@ -0,0 +1,69 @@
+use std::{
+    sync::{
+        atomic::Ordering,
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use color_eyre::{eyre::eyre, Result};
+
+use crate::file::{Progress, Records};
+
+/// Work that goes through all the rows of a sheet, e.g. to sort
+/// them, done on a background thread as it can take a while.
+/// It's cancelled if dropped before it's done.
+pub struct Scan<T> {
+    progress: Arc<Progress>,
+    n_rows: usize,
+    rx: Receiver<Result<T>>,
+}
+impl<T: Send + 'static> Scan<T> {
+    pub fn start(
+        records: &Arc<Records>,
+        f: impl FnOnce(&Records, &Arc<Progress>) -> Result<T> + Send + 'static,
+    ) -> Self {
+        let progress = Arc::new(Progress::default());
+        let (tx, rx) = mpsc::channel();
+        let n_rows = records.len();
+        {
+            let records = records.clone();
+            let progress = progress.clone();
+            thread::spawn(move || {
+                let _ = tx.send(f(&records, &progress));
+            });
+        }
+        Scan {
+            progress,
+            n_rows,
+            rx,
+        }
+    }
+
+    /// The result if the scan is done, waiting up to `timeout` for it.
+    pub fn result(&self, timeout: Duration) -> Option<Result<T>> {
+        match self.rx.recv_timeout(timeout) {
+            Ok(result) => Some(result),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => Some(Err(eyre!("The scan stopped."))),
+        }
+    }
+}
+impl<T> Scan<T> {
+    /// How much of the rows have been gone through, from 0 to 1.
+    pub fn ratio(&self) -> f64 {
+        let rows = self.progress.rows_parsed.load(Ordering::Relaxed);
+        if self.n_rows == 0 {
+            0.
+        } else {
+            (rows as f64 / self.n_rows as f64).min(1.)
+        }
+    }
+}
+impl<T> Drop for Scan<T> {
+    fn drop(&mut self) {
+        self.progress.cancel();
+    }
+}
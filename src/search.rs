@@ -0,0 +1,209 @@
+use std::{fmt, ops::Range, sync::Arc};
+
+use color_eyre::{eyre::eyre, Result};
+use regex::{Regex, RegexBuilder};
+
+use crate::file::{Progress, Records};
+
+/// How the search text is matched against cells.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SearchMode {
+    #[default]
+    IgnoreCase,
+    MatchCase,
+    Regex,
+}
+impl SearchMode {
+    pub fn next(self) -> Self {
+        match self {
+            SearchMode::IgnoreCase => SearchMode::MatchCase,
+            SearchMode::MatchCase => SearchMode::Regex,
+            SearchMode::Regex => SearchMode::IgnoreCase,
+        }
+    }
+}
+impl fmt::Display for SearchMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SearchMode::IgnoreCase => "ignore case",
+            SearchMode::MatchCase => "match case",
+            SearchMode::Regex => "regex",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Text to find in cells.
+#[derive(Debug, Clone)]
+pub struct Search {
+    regex: Regex,
+}
+impl Search {
+    pub fn new(query: &str, mode: SearchMode) -> Result<Self> {
+        let pattern = match mode {
+            SearchMode::Regex => query.to_string(),
+            _ => regex::escape(query),
+        };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(mode == SearchMode::IgnoreCase)
+            .build()
+            .map_err(|_| eyre!("Invalid regex"))?;
+        Ok(Self { regex })
+    }
+
+    /// Where the search text occurs in the cell.
+    pub fn find<'a>(&'a self, cell: &'a str) -> impl Iterator<Item = Range<usize>> + 'a {
+        self.regex
+            .find_iter(cell)
+            .filter(|m| !m.is_empty())
+            .map(|m| m.range())
+    }
+
+    pub fn is_match(&self, cell: &str) -> bool {
+        self.find(cell).next().is_some()
+    }
+
+    /// The cells that match, as (row, column) pairs
+    /// in the order the records are stored.
+    pub fn find_cells(
+        &self,
+        records: &Records,
+        progress: &Arc<Progress>,
+    ) -> Result<Vec<(usize, usize)>> {
+        let mut cells = vec![];
+        records.for_each(progress, |idx, row| {
+            for (col, cell) in row.iter().enumerate() {
+                if self.is_match(cell) {
+                    cells.push((idx, col));
+                }
+            }
+        })?;
+        Ok(cells)
+    }
+}
+
+/// The match after the `cursor` cell, wrapping around to the first.
+/// Matches are (row, column) pairs in the order they're shown.
+pub fn next_match(matches: &[(usize, usize)], cursor: (usize, usize)) -> Option<(usize, usize)> {
+    matches
+        .iter()
+        .find(|cell| **cell > cursor)
+        .or(matches.first())
+        .copied()
+}
+
+/// The match before the `cursor` cell, wrapping around to the last.
+pub fn previous_match(
+    matches: &[(usize, usize)],
+    cursor: (usize, usize),
+) -> Option<(usize, usize)> {
+    matches
+        .iter()
+        .rev()
+        .find(|cell| **cell < cursor)
+        .or(matches.last())
+        .copied()
+}
+
+/// Which match the `cursor` is on out of how many.
+pub fn describe_matches(matches: &[(usize, usize)], cursor: (usize, usize)) -> String {
+    match matches.iter().position(|cell| *cell == cursor) {
+        _ if matches.is_empty() => "no matches".to_string(),
+        Some(i) => format!("match {} of {}", i + 1, matches.len()),
+        None => format!("{} matches", matches.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use csv::StringRecord;
+
+    use super::*;
+    use crate::file::Rows;
+
+    /// Where the matches start and end.
+    fn found(query: &str, mode: SearchMode, cell: &str) -> Vec<(usize, usize)> {
+        Search::new(query, mode)
+            .unwrap()
+            .find(cell)
+            .map(|range| (range.start, range.end))
+            .collect()
+    }
+
+    #[test]
+    fn cycles_through_modes() {
+        let mode = SearchMode::default();
+        assert_eq!(mode, SearchMode::IgnoreCase);
+        assert_eq!(mode.next(), SearchMode::MatchCase);
+        assert_eq!(mode.next().next(), SearchMode::Regex);
+        assert_eq!(mode.next().next().next(), SearchMode::IgnoreCase);
+    }
+
+    #[test]
+    fn matches_text_ignoring_or_matching_case() {
+        assert_eq!(
+            found("db", SearchMode::IgnoreCase, "DB-1, db-2"),
+            [(0, 2), (6, 8)]
+        );
+        assert_eq!(found("db", SearchMode::MatchCase, "DB-1, db-2"), [(6, 8)]);
+
+        // Outside of regex mode the text is taken literally.
+        assert!(found("a.c", SearchMode::IgnoreCase, "abc").is_empty());
+        assert_eq!(found("a.c", SearchMode::MatchCase, "x a.c"), [(2, 5)]);
+        assert!(Search::new("(", SearchMode::MatchCase).is_ok());
+    }
+
+    #[test]
+    fn matches_regexes() {
+        assert_eq!(found(r"^db-\d+$", SearchMode::Regex, "db-12"), [(0, 5)]);
+        assert!(found(r"^db-\d+$", SearchMode::Regex, "DB-12").is_empty());
+        assert_eq!(found("(?i)db", SearchMode::Regex, "DB"), [(0, 2)]);
+        assert!(Search::new("(", SearchMode::Regex).is_err());
+
+        // Empty matches aren't counted, as there'd be nothing to show.
+        assert_eq!(found("x*", SearchMode::Regex, "axxb"), [(1, 3)]);
+        assert!(!Search::new("x*", SearchMode::Regex).unwrap().is_match("ab"));
+    }
+
+    #[test]
+    fn finds_matching_cells() {
+        let records = Records {
+            headers: StringRecord::from(vec!["host", "status"]),
+            types: None,
+            columns: vec![],
+            rows: Rows::Loaded(vec![
+                StringRecord::from(vec!["db-1", "ok"]),
+                StringRecord::from(vec!["web-1", "error"]),
+                StringRecord::from(vec!["db-2", "error"]),
+            ]),
+            errors: vec![],
+        };
+        let search = Search::new("r", SearchMode::IgnoreCase).unwrap();
+        let cells = search
+            .find_cells(&records, &Arc::new(Progress::default()))
+            .unwrap();
+        assert_eq!(cells, [(1, 1), (2, 1)]);
+    }
+
+    #[test]
+    fn moves_between_matches_wrapping_around() {
+        let matches = [(0, 2), (3, 0), (3, 1)];
+        assert_eq!(next_match(&matches, (0, 0)), Some((0, 2)));
+        assert_eq!(next_match(&matches, (0, 2)), Some((3, 0)));
+        assert_eq!(next_match(&matches, (3, 1)), Some((0, 2)));
+        assert_eq!(next_match(&matches, (9, 0)), Some((0, 2)));
+        assert_eq!(previous_match(&matches, (3, 1)), Some((3, 0)));
+        assert_eq!(previous_match(&matches, (1, 0)), Some((0, 2)));
+        assert_eq!(previous_match(&matches, (0, 2)), Some((3, 1)));
+        assert_eq!(next_match(&[], (0, 0)), None);
+        assert_eq!(previous_match(&[], (0, 0)), None);
+    }
+
+    #[test]
+    fn counts_matches() {
+        let matches = [(0, 2), (3, 0), (3, 1)];
+        assert_eq!(describe_matches(&matches, (3, 0)), "match 2 of 3");
+        assert_eq!(describe_matches(&matches, (1, 1)), "3 matches");
+        assert_eq!(describe_matches(&[], (0, 0)), "no matches");
+    }
+}
@@ -0,0 +1,133 @@
+use std::{cmp::Ordering, sync::Arc};
+
+use color_eyre::Result;
+
+use crate::{
+    file::{Progress, Records},
+    value::Value,
+};
+
+/// A column to sort rows by. Its name is kept to find
+/// the column again if the headers change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    pub col: usize,
+    pub name: String,
+    pub descending: bool,
+}
+
+/// Order the rows (as indices into the records) by each key in turn.
+/// Empty cells go last whichever the direction, and rows that
+/// compare equal keep their original order.
+pub fn sort_rows(
+    records: &Records,
+    rows: &mut [usize],
+    keys: &[SortKey],
+    progress: &Arc<Progress>,
+) -> Result<()> {
+    // Only the cells in the key columns are kept. Records that are
+    // still being indexed can grow, so rows are only counted as visited.
+    let mut cells: Vec<Vec<String>> = Vec::with_capacity(records.len());
+    records.for_each(progress, |_, row| {
+        cells.push(
+            keys.iter()
+                .map(|key| row.get(key.col).unwrap_or_default().to_string())
+                .collect(),
+        );
+    })?;
+    let values: Vec<Vec<_>> = cells
+        .iter()
+        .map(|row| row.iter().map(|cell| Value::parse(cell)).collect())
+        .collect();
+
+    rows.sort_by(|a, b| {
+        let (a, b) = (&values[*a], &values[*b]);
+        keys.iter()
+            .enumerate()
+            .map(|(i, key)| match (a.get(i), b.get(i)) {
+                (Some(Value::Null) | None, Some(Value::Null) | None) => Ordering::Equal,
+                (Some(Value::Null) | None, _) => Ordering::Greater,
+                (_, Some(Value::Null) | None) => Ordering::Less,
+                (Some(a), Some(b)) if key.descending => b.compare(a),
+                (Some(a), Some(b)) => a.compare(b),
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Write};
+
+    use csv::StringRecord;
+
+    use super::*;
+    use crate::{dialect::Dialect, file::Rows, index::RowIndex};
+
+    fn loaded(rows: &[&str]) -> Records {
+        Records {
+            headers: StringRecord::from(vec!["value"]),
+            types: None,
+            columns: vec![],
+            rows: Rows::Loaded(
+                rows.iter()
+                    .map(|row| StringRecord::from(vec![*row]))
+                    .collect(),
+            ),
+            errors: vec![],
+        }
+    }
+
+    fn sorted(records: &Records, descending: bool) -> Vec<usize> {
+        let mut rows: Vec<_> = (0..records.len()).collect();
+        let key = SortKey {
+            col: 0,
+            name: "value".to_string(),
+            descending,
+        };
+        sort_rows(records, &mut rows, &[key], &Arc::default()).unwrap();
+        rows
+    }
+
+    #[test]
+    fn sorts_by_value_with_empty_cells_last() {
+        let records = loaded(&["10", "", "9", "b", "A", "2024-01-01"]);
+        assert_eq!(sorted(&records, false), [2, 0, 5, 4, 3, 1]);
+        assert_eq!(sorted(&records, true), [3, 4, 5, 0, 2, 1]);
+    }
+
+    #[test]
+    fn sorts_rows_still_being_indexed() {
+        let path = std::env::temp_dir().join(format!("tabv-sort-{}.csv", std::process::id()));
+        let mut file = fs::File::create(&path).unwrap();
+        writeln!(file, "value").unwrap();
+        for i in 0..200_000 {
+            writeln!(file, "{}", 200_000 - i).unwrap();
+        }
+        drop(file);
+
+        // Rows keep being indexed while the ones so far are sorted.
+        let (headers, index) = RowIndex::build(&path, &Dialect::default()).unwrap();
+        let records = Records {
+            headers,
+            types: None,
+            columns: vec![],
+            rows: Rows::Indexed(index),
+            errors: vec![],
+        };
+        let n_rows = records.len();
+        let mut rows: Vec<_> = (0..n_rows).collect();
+        let key = SortKey {
+            col: 0,
+            name: "value".to_string(),
+            descending: false,
+        };
+        sort_rows(&records, &mut rows, &[key], &Arc::default()).unwrap();
+
+        // The values count down, so sorting reverses the rows.
+        assert_eq!(rows, (0..n_rows).rev().collect::<Vec<_>>());
+        fs::remove_file(path).unwrap();
+    }
+}
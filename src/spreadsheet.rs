@@ -0,0 +1,265 @@
+use std::{path::Path, sync::atomic::Ordering};
+
+use calamine::{open_workbook_auto, Data, DataType, Reader};
+use chrono::{Duration, NaiveDateTime, Timelike};
+use color_eyre::Result;
+use csv::StringRecord;
+
+use crate::file::{LoadOptions, Progress, Records, Rows, Sheets};
+
+/// Load each worksheet of a workbook as its own sheet.
+pub fn load_workbook(path: &Path, options: &LoadOptions, progress: &Progress) -> Result<Sheets> {
+    let size = fs_err::metadata(path)?.len();
+    progress.total_bytes.store(size, Ordering::Relaxed);
+
+    let mut workbook = open_workbook_auto(path)?;
+    let names = workbook.sheet_names();
+    let has_header = options.dialect.has_header.unwrap_or(true);
+    let mut sheets = vec![];
+    let mut n_rows = 0;
+    for (i, name) in names.iter().enumerate() {
+        let range = workbook.worksheet_range(name)?;
+        let mut rows = range
+            .rows()
+            .map(|row| row.iter().map(format_cell).collect::<StringRecord>());
+
+        let headers = if has_header {
+            rows.next().unwrap_or_default()
+        } else {
+            (0..range.width())
+                .map(|i| options.dialect.column_names.name(i))
+                .collect()
+        };
+        let rows: Vec<_> = rows.collect();
+
+        n_rows += rows.len();
+        progress.bytes_read.store(
+            size * (i + 1) as u64 / names.len() as u64,
+            Ordering::Relaxed,
+        );
+        progress.update(n_rows)?;
+
+        sheets.push((
+            name.clone(),
+            Records {
+                headers,
+                types: None,
+                columns: vec![],
+                rows: Rows::Loaded(rows),
+                errors: vec![],
+            },
+        ));
+    }
+    Ok(sheets)
+}
+
+/// Cells hold the last computed value of any formula,
+/// so that's what's shown rather than the formula itself.
+fn format_cell(cell: &Data) -> String {
+    // Excel stores dates and times as a number of days since 1900
+    // and OpenDocument as ISO 8601 text, so both are converted back
+    // to be shown the same way.
+    let formatted = match cell {
+        Data::DateTime(dt) if dt.is_duration() => cell.as_duration().map(format_duration),
+        Data::DateTime(_) | Data::DateTimeIso(_) => cell.as_datetime().map(format_datetime),
+        Data::DurationIso(_) => cell.as_duration().map(format_duration),
+        _ => None,
+    };
+    formatted.unwrap_or_else(|| cell.to_string())
+}
+
+fn format_datetime(dt: NaiveDateTime) -> String {
+    if dt.num_seconds_from_midnight() == 0 {
+        dt.date().to_string()
+    } else {
+        dt.to_string()
+    }
+}
+
+/// Durations are shown as hours, which may run past a day.
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.num_seconds();
+    let sign = if secs < 0 { "-" } else { "" };
+    let secs = secs.abs();
+    format!(
+        "{}{}:{:02}:{:02}",
+        sign,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, path::PathBuf};
+
+    use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+    use super::*;
+
+    /// Zip up the parts of a workbook, each a path and its XML.
+    /// Fixtures are kept to just what calamine needs to read them.
+    fn write_zip(name: &str, ext: &str, parts: &[(String, String)]) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("tabv-{}-{}.{}", name, std::process::id(), ext));
+        let mut zip = ZipWriter::new(std::fs::File::create(&path).unwrap());
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        for (name, content) in parts {
+            zip.start_file(name.as_str(), options).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        path
+    }
+
+    /// Each sheet's rows are given as `<row>` elements.
+    fn write_xlsx(name: &str, sheets: &[(&str, &str)]) -> PathBuf {
+        let ns = r#"xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships""#;
+        let rel = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+        let mut parts = vec![
+            (
+                "[Content_Types].xml".to_string(),
+                r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="xml" ContentType="application/xml"/><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/></Types>"#
+                    .to_string(),
+            ),
+            (
+                "_rels/.rels".to_string(),
+                format!(
+                    r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="{rel}/officeDocument" Target="xl/workbook.xml"/></Relationships>"#
+                ),
+            ),
+            (
+                "xl/styles.xml".to_string(),
+                // Builtin formats 14, 22 and 46 are a date, a date and
+                // time, and a duration.
+                format!(
+                    r#"<styleSheet {ns}><cellXfs count="4"><xf numFmtId="0"/><xf numFmtId="14" applyNumberFormat="1"/><xf numFmtId="22" applyNumberFormat="1"/><xf numFmtId="46" applyNumberFormat="1"/></cellXfs></styleSheet>"#
+                ),
+            ),
+        ];
+        let mut names = String::new();
+        let mut rels =
+            format!(r#"<Relationship Id="rIdStyles" Type="{rel}/styles" Target="styles.xml"/>"#);
+        for (i, (name, rows)) in sheets.iter().enumerate() {
+            let n = i + 1;
+            names.push_str(&format!(
+                r#"<sheet name="{name}" sheetId="{n}" r:id="rId{n}"/>"#
+            ));
+            rels.push_str(&format!(
+                r#"<Relationship Id="rId{n}" Type="{rel}/worksheet" Target="worksheets/sheet{n}.xml"/>"#
+            ));
+            parts.push((
+                format!("xl/worksheets/sheet{n}.xml"),
+                format!(r#"<worksheet {ns}><sheetData>{rows}</sheetData></worksheet>"#),
+            ));
+        }
+        parts.push((
+            "xl/workbook.xml".to_string(),
+            format!(r#"<workbook {ns}><sheets>{names}</sheets></workbook>"#),
+        ));
+        parts.push((
+            "xl/_rels/workbook.xml.rels".to_string(),
+            format!(
+                r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">{rels}</Relationships>"#
+            ),
+        ));
+        write_zip(name, "xlsx", &parts)
+    }
+
+    fn load(path: &Path) -> Vec<(String, Vec<StringRecord>)> {
+        let options = LoadOptions::default();
+        load_workbook(path, &options, &Progress::default())
+            .unwrap()
+            .into_iter()
+            .map(|(name, records)| {
+                let mut rows = vec![records.headers.clone()];
+                rows.extend(records.window(0..records.len()).iter().cloned());
+                (name, rows)
+            })
+            .collect()
+    }
+
+    fn row(cells: &[&str]) -> StringRecord {
+        StringRecord::from(cells.to_vec())
+    }
+
+    #[test]
+    fn loads_each_xlsx_worksheet() {
+        let path = write_xlsx(
+            "sheets",
+            &[
+                (
+                    "People",
+                    r#"<row r="1"><c r="A1" t="inlineStr"><is><t>name</t></is></c><c r="B1" t="inlineStr"><is><t>age</t></is></c></row><row r="2"><c r="A2" t="inlineStr"><is><t>ann</t></is></c><c r="B2"><v>41</v></c></row>"#,
+                ),
+                (
+                    "Totals",
+                    r#"<row r="1"><c r="A1" t="inlineStr"><is><t>sum</t></is></c></row><row r="2"><c r="A2"><f>SUM(People!B2:B2)+1</f><v>42</v></c></row>"#,
+                ),
+            ],
+        );
+        let sheets = load(&path);
+
+        let names: Vec<_> = sheets.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["People", "Totals"]);
+        assert_eq!(sheets[0].1, [row(&["name", "age"]), row(&["ann", "41"])]);
+        // The formula's cached value is shown, not the formula.
+        assert_eq!(sheets[1].1, [row(&["sum"]), row(&["42"])]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn formats_xlsx_dates_and_durations() {
+        let path = write_xlsx(
+            "dates",
+            &[(
+                "Sheet1",
+                r#"<row r="1"><c r="A1" t="inlineStr"><is><t>date</t></is></c><c r="B1" t="inlineStr"><is><t>when</t></is></c><c r="C1" t="inlineStr"><is><t>took</t></is></c></row><row r="2"><c r="A2" s="1"><v>45322</v></c><c r="B2" s="2"><v>45322.5</v></c><c r="C2" s="3"><v>1.0625</v></c></row><row r="3"><c r="A3" s="1"><v>45323</v></c><c r="B3" s="2"><v>45323.75</v></c><c r="C3" s="3"><v>-0.0625</v></c></row>"#,
+            )],
+        );
+        let sheets = load(&path);
+
+        assert_eq!(
+            sheets[0].1[1..],
+            [
+                row(&["2024-01-31", "2024-01-31 12:00:00", "25:30:00"]),
+                row(&["2024-02-01", "2024-02-01 18:00:00", "-1:30:00"]),
+            ]
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn loads_ods_sheets() {
+        let content = r#"<office:document-content xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" xmlns:table="urn:oasis:names:tc:opendocument:xmlns:table:1.0" xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0" office:version="1.2"><office:body><office:spreadsheet><table:table table:name="Log"><table:table-row><table:table-cell office:value-type="string"><text:p>when</text:p></table:table-cell><table:table-cell office:value-type="string"><text:p>took</text:p></table:table-cell><table:table-cell office:value-type="string"><text:p>total</text:p></table:table-cell></table:table-row><table:table-row><table:table-cell office:value-type="date" office:date-value="2024-01-31T12:30:00"><text:p>31/01/24</text:p></table:table-cell><table:table-cell office:value-type="time" office:time-value="PT01H30M00S"><text:p>01:30</text:p></table:table-cell><table:table-cell table:formula="of:=1+2" office:value-type="float" office:value="3"><text:p>3</text:p></table:table-cell></table:table-row></table:table><table:table table:name="Empty"/></office:spreadsheet></office:body></office:document-content>"#;
+        let path = write_zip(
+            "sheets",
+            "ods",
+            &[
+                (
+                    "mimetype".to_string(),
+                    "application/vnd.oasis.opendocument.spreadsheet".to_string(),
+                ),
+                (
+                    "META-INF/manifest.xml".to_string(),
+                    r#"<manifest:manifest xmlns:manifest="urn:oasis:names:tc:opendocument:xmlns:manifest:1.0"><manifest:file-entry manifest:full-path="/" manifest:media-type="application/vnd.oasis.opendocument.spreadsheet"/><manifest:file-entry manifest:full-path="content.xml" manifest:media-type="text/xml"/></manifest:manifest>"#
+                        .to_string(),
+                ),
+                ("content.xml".to_string(), content.to_string()),
+            ],
+        );
+        let sheets = load(&path);
+
+        let names: Vec<_> = sheets.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["Log", "Empty"]);
+        assert_eq!(
+            sheets[0].1,
+            [
+                row(&["when", "took", "total"]),
+                row(&["2024-01-31 12:30:00", "1:30:00", "3"]),
+            ]
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+}
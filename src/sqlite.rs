@@ -0,0 +1,294 @@
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc, Mutex},
+};
+
+use color_eyre::Result;
+use csv::StringRecord;
+use rusqlite::{types::ValueRef, Connection, OpenFlags};
+
+use crate::file::{Progress, Records, RowWindow, Rows, Sheets, PROGRESS_INTERVAL};
+
+/// How many steps a statement takes between
+/// checks for whether loading was cancelled.
+const CANCEL_CHECK_OPS: i32 = 100_000;
+
+/// Rows of a table, view or query, read from
+/// the database only when they're needed.
+#[derive(Debug)]
+pub struct SqliteRows {
+    path: PathBuf,
+    conn: Arc<Mutex<Connection>>,
+    sql: String,
+    len: usize,
+    window: RowWindow,
+}
+impl SqliteRows {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Get the rows in the provided range,
+    /// querying them if they aren't cached.
+    pub fn rows(&self, range: Range<usize>) -> Vec<StringRecord> {
+        self.window
+            .rows(range, self.len, |start, count| self.read_rows(start, count))
+    }
+
+    pub fn read_error(&self) -> Option<String> {
+        self.window.error()
+    }
+
+    /// Go through all the rows in order with a single statement, on
+    /// a connection of its own so that rows can still be read for
+    /// the screen meanwhile. Reading a window at a time would get
+    /// slower the further in it is, as skipped rows are still read.
+    pub fn for_each(
+        &self,
+        progress: &Arc<Progress>,
+        mut f: impl FnMut(usize, &StringRecord),
+    ) -> Result<()> {
+        let conn = Connection::open_with_flags(&self.path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let cancelled = progress.clone();
+        conn.progress_handler(CANCEL_CHECK_OPS, Some(move || cancelled.is_cancelled()))?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT * FROM {} LIMIT {}",
+            subquery(&self.sql),
+            self.len
+        ))?;
+        let n_cols = stmt.column_count();
+        let mut rows = stmt.query([])?;
+        let mut idx = 0;
+        while let Some(row) = rows.next()? {
+            let record = (0..n_cols)
+                .map(|i| row.get_ref(i).map(format_value))
+                .collect::<rusqlite::Result<StringRecord>>()?;
+            f(idx, &record);
+            idx += 1;
+            if idx % PROGRESS_INTERVAL == 0 {
+                progress.update(idx)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_rows(&self, start: usize, count: usize) -> Result<Vec<StringRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT * FROM {} LIMIT {} OFFSET {}",
+            subquery(&self.sql),
+            count,
+            start
+        ))?;
+        let n_cols = stmt.column_count();
+        let rows = stmt
+            .query_map([], |row| {
+                (0..n_cols)
+                    .map(|i| row.get_ref(i).map(format_value))
+                    .collect::<rusqlite::Result<StringRecord>>()
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(rows)
+    }
+}
+
+fn open(path: &Path) -> Result<Arc<Mutex<Connection>>> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    Ok(Arc::new(Mutex::new(conn)))
+}
+
+/// Open the database for loading. Counting the rows of a big table
+/// or query can take a while, so statements are interrupted if
+/// loading is cancelled, until [`stop_cancelling`] is called.
+fn open_cancellable(path: &Path, progress: &Arc<Progress>) -> Result<Arc<Mutex<Connection>>> {
+    let conn = open(path)?;
+    let cancelled = progress.clone();
+    conn.lock()
+        .unwrap()
+        .progress_handler(CANCEL_CHECK_OPS, Some(move || cancelled.is_cancelled()))?;
+    Ok(conn)
+}
+
+/// Stop checking for cancellation once loading is done,
+/// as the connection is kept to read rows on demand.
+fn stop_cancelling(conn: &Arc<Mutex<Connection>>) -> Result<()> {
+    conn.lock()
+        .unwrap()
+        .progress_handler(0, None::<fn() -> bool>)?;
+    Ok(())
+}
+
+/// Load each table and view of the database as a sheet,
+/// followed by the results of any queries run against it.
+pub fn load_sqlite(path: &Path, queries: &[String], progress: &Arc<Progress>) -> Result<Sheets> {
+    let size = fs_err::metadata(path)?.len();
+    progress.total_bytes.store(size, Ordering::Relaxed);
+    let conn = open_cancellable(path, progress)?;
+
+    let names = conn
+        .lock()
+        .unwrap()
+        .prepare(
+            "SELECT name FROM sqlite_master
+            WHERE type IN ('table', 'view') AND name NOT LIKE 'sqlite_%'
+            ORDER BY name",
+        )?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut sheets = vec![];
+    let mut n_rows = 0;
+    for name in names {
+        let sql = format!("SELECT * FROM \"{}\"", name.replace('"', "\"\""));
+        let records = records(path, &conn, sql)?;
+        n_rows += records.len();
+        progress.update(n_rows)?;
+        sheets.push((name, records));
+    }
+    for (i, sql) in queries.iter().enumerate() {
+        let records = records(path, &conn, sql.clone())?;
+        n_rows += records.len();
+        progress.update(n_rows)?;
+        sheets.push((query_name(i), records));
+    }
+    stop_cancelling(&conn)?;
+    progress.bytes_read.store(size, Ordering::Relaxed);
+    Ok(sheets)
+}
+
+/// Run a query against the database, with its result as a sheet
+/// to add to those already loaded, where `idx` is the query's
+/// index among those run against it.
+pub fn load_query(path: &Path, sql: &str, idx: usize, progress: &Arc<Progress>) -> Result<Sheets> {
+    let conn = open_cancellable(path, progress)?;
+    let records = records(path, &conn, sql.to_string())?;
+    stop_cancelling(&conn)?;
+    progress.update(records.len())?;
+    Ok(vec![(query_name(idx), records)])
+}
+
+/// What to call the sheet with the result of the `idx`th query.
+pub fn query_name(idx: usize) -> String {
+    format!("Query {}", idx + 1)
+}
+
+/// Check that a query is valid without running it. It's checked
+/// as a subquery, which is how it's run, as some statements
+/// (e.g. `PRAGMA`) are only valid on their own.
+pub fn check_query(path: &Path, sql: &str) -> Result<()> {
+    let conn = open(path)?;
+    let conn = conn.lock().unwrap();
+    let sql = sql.trim().trim_end_matches(';');
+    conn.prepare(&format!("SELECT * FROM {}", subquery(sql)))?;
+    Ok(())
+}
+
+/// Wrap a statement in a subquery so that its rows can be counted and
+/// read a window at a time. The closing parenthesis goes on a line of
+/// its own so that it isn't commented out by a trailing `--` comment.
+fn subquery(sql: &str) -> String {
+    format!("(\n{}\n)", sql)
+}
+
+fn records(path: &Path, conn: &Arc<Mutex<Connection>>, sql: String) -> Result<Records> {
+    // Trailing semicolons would break the statement
+    // once it's wrapped in a subquery.
+    let sql = sql.trim().trim_end_matches(';').to_string();
+
+    let (headers, types, len) = {
+        let conn = conn.lock().unwrap();
+        let stmt = conn.prepare(&sql)?;
+        let headers: StringRecord = stmt.column_names().into_iter().collect();
+        let types: StringRecord = stmt
+            .columns()
+            .iter()
+            .map(|col| col.decl_type().unwrap_or_default())
+            .collect();
+        let len: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM {}", subquery(&sql)),
+            [],
+            |row| row.get(0),
+        )?;
+        (headers, types, len as usize)
+    };
+
+    // Columns of views and queries often have no declared type.
+    let types = Some(types).filter(|types| types.iter().any(|ty| !ty.is_empty()));
+    Ok(Records {
+        headers,
+        types,
+        columns: vec![],
+        rows: Rows::Sqlite(SqliteRows {
+            path: path.to_path_buf(),
+            conn: conn.clone(),
+            sql,
+            len,
+            window: RowWindow::default(),
+        }),
+        errors: vec![],
+    })
+}
+
+fn format_value(value: ValueRef) -> String {
+    match value {
+        ValueRef::Null => String::new(),
+        ValueRef::Integer(i) => i.to_string(),
+        ValueRef::Real(f) => f.to_string(),
+        ValueRef::Text(text) => String::from_utf8_lossy(text).to_string(),
+        ValueRef::Blob(blob) => format!("<{} bytes>", blob.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_db(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("tabv-{}-{}.db", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE t (a INTEGER, b TEXT);
+            INSERT INTO t VALUES (1, 'x'), (2, 'y'), (3, NULL);",
+        )
+        .unwrap();
+        path
+    }
+
+    #[test]
+    fn rejects_queries_that_cant_be_subqueries() {
+        let path = write_db("pragma");
+        let err = check_query(&path, "PRAGMA table_info(t)").unwrap_err();
+        assert!(err.to_string().contains("near \"(\": syntax error"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn runs_queries_ending_in_comments() {
+        let path = write_db("comment");
+        let sql = "select a from t -- note";
+        check_query(&path, sql).unwrap();
+
+        let sheets = load_sqlite(&path, &[sql.to_string()], &Arc::default()).unwrap();
+        let (name, records) = sheets.last().unwrap();
+        assert_eq!(name, "Query 1");
+        let values: Vec<_> = records
+            .window(0..records.len())
+            .iter()
+            .map(|row| row[0].to_string())
+            .collect();
+        assert_eq!(values, ["1", "2", "3"]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reports_queries_failing_as_they_run() {
+        let path = write_db("runtime");
+        let sql = "select a from t where abs(-9223372036854775808) > a";
+        check_query(&path, sql).unwrap();
+        let err = load_query(&path, sql, 0, &Arc::default()).unwrap_err();
+        assert_eq!(err.to_string(), "integer overflow");
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+use std::cmp::Ordering;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+
+/// Date and time formats that are recognized,
+/// besides RFC 3339 (e.g. `2024-01-31T12:00:00+01:00`).
+const DATETIME_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M",
+    "%Y/%m/%d %H:%M:%S%.f",
+];
+const DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%Y/%m/%d"];
+
+/// A cell's value, interpreted by what it looks like.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value<'a> {
+    Null,
+    Number(f64),
+    DateTime(NaiveDateTime),
+    Text(&'a str),
+}
+impl<'a> Value<'a> {
+    pub fn parse(s: &'a str) -> Self {
+        let trimmed = s.trim();
+        if trimmed.is_empty() {
+            Value::Null
+        } else if let Some(number) = parse_number(trimmed) {
+            Value::Number(number)
+        } else if let Some(datetime) = parse_datetime(trimmed) {
+            Value::DateTime(datetime)
+        } else {
+            Value::Text(s)
+        }
+    }
+
+    /// Compare values of the same kind by their value,
+    /// with numbers before dates before text.
+    /// Text is compared ignoring case.
+    pub fn compare(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a.total_cmp(b),
+            (Value::DateTime(a), Value::DateTime(b)) => a.cmp(b),
+            (Value::Text(a), Value::Text(b)) => {
+                let lower = |s: &'a str| s.chars().flat_map(char::to_lowercase);
+                lower(a).cmp(lower(b)).then_with(|| a.cmp(b))
+            }
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            Value::Number(_) => 0,
+            Value::DateTime(_) => 1,
+            Value::Text(_) => 2,
+            Value::Null => 3,
+        }
+    }
+}
+
+/// Numbers are plain decimals, optionally in scientific
+/// notation; `inf` and `NaN` are treated as text.
+fn parse_number(s: &str) -> Option<f64> {
+    let digits = s.strip_prefix(['-', '+']).unwrap_or(s);
+    if !digits.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+    s.parse().ok()
+}
+
+fn parse_datetime(s: &str) -> Option<NaiveDateTime> {
+    // Dates all start with the year.
+    if !s.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(s) {
+        return Some(datetime.naive_utc());
+    }
+    DATETIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+        .or_else(|| parse_date(s).and_then(|date| date.and_hms_opt(0, 0, 0)))
+}
+
+/// Parse a date without a time.
+pub fn parse_date(s: &str) -> Option<NaiveDate> {
+    DATE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(s, format).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compare(a: &str, b: &str) -> Ordering {
+        Value::parse(a).compare(&Value::parse(b))
+    }
+
+    #[test]
+    fn parses_values_by_what_they_look_like() {
+        assert_eq!(Value::parse("  "), Value::Null);
+        assert_eq!(Value::parse(" -1.5e3 "), Value::Number(-1500.));
+        assert_eq!(Value::parse(".5"), Value::Number(0.5));
+        assert_eq!(Value::parse("inf"), Value::Text("inf"));
+        assert_eq!(Value::parse("NaN"), Value::Text("NaN"));
+        assert_eq!(Value::parse("1st"), Value::Text("1st"));
+        let date = NaiveDate::from_ymd_opt(2024, 1, 31).unwrap();
+        assert_eq!(
+            Value::parse("2024/01/31"),
+            Value::DateTime(date.and_hms_opt(0, 0, 0).unwrap())
+        );
+        assert_eq!(
+            Value::parse("2024-01-31T12:00:00+01:00"),
+            Value::DateTime(date.and_hms_opt(11, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn compares_numbers_by_value() {
+        assert_eq!(compare("9", "10"), Ordering::Less);
+        assert_eq!(compare("-2", "-10"), Ordering::Greater);
+        assert_eq!(compare("1.0", "1"), Ordering::Equal);
+        assert_eq!(compare("1e3", "999"), Ordering::Greater);
+    }
+
+    #[test]
+    fn compares_dates_by_value() {
+        assert_eq!(compare("2024-01-15", "2023-12-31"), Ordering::Greater);
+        assert_eq!(compare("2024-01-15", "2024-01-15 00:00"), Ordering::Equal);
+        assert_eq!(compare("2024/02/01", "2024-01-31 23:59"), Ordering::Greater);
+    }
+
+    #[test]
+    fn compares_text_ignoring_case_first() {
+        assert_eq!(compare("apple", "Banana"), Ordering::Less);
+        assert_eq!(compare("Apple", "apple"), Ordering::Less);
+        assert_eq!(compare("straße", "STRASSE"), Ordering::Greater);
+    }
+
+    #[test]
+    fn orders_numbers_before_dates_before_text() {
+        assert_eq!(compare("100", "2024-01-01"), Ordering::Less);
+        assert_eq!(compare("2024-01-01", "abc"), Ordering::Less);
+        assert_eq!(compare("abc", "1"), Ordering::Greater);
+    }
+}
@@ -0,0 +1,149 @@
+use ratatui::{
+    prelude::*,
+    widgets::{Block, BorderType, Clear, List, ListItem, ListState, Paragraph},
+};
+use unicode_width::UnicodeWidthStr;
+
+use crate::infer::{ColumnInfo, ColumnType};
+
+use super::finder::popup_area;
+
+/// A column of the current sheet, with its types.
+pub struct Column {
+    pub name: String,
+
+    /// The type declared by the file, if any.
+    pub declared: Option<String>,
+    pub info: ColumnInfo,
+}
+
+/// Lists the columns with the types inferred for them.
+#[derive(Default)]
+pub struct ColumnsView {
+    columns: Vec<Column>,
+
+    // Whether the types were inferred from only the first rows.
+    sampled: bool,
+    list_state: ListState,
+    selected_column: usize,
+}
+impl ColumnsView {
+    pub fn set_columns(&mut self, columns: Vec<Column>, sampled: bool, selected: Option<usize>) {
+        self.columns = columns;
+        self.sampled = sampled;
+        self.selected_column = selected.unwrap_or_default();
+    }
+
+    fn render_columns(&mut self, area: Rect, buf: &mut Buffer) {
+        if self.columns.is_empty() {
+            Line::raw("No columns.")
+                .style(Color::DarkGray)
+                .render(area, buf);
+            return;
+        }
+
+        let name_width = self
+            .columns
+            .iter()
+            .map(|column| column.name.width())
+            .max()
+            .unwrap_or_default();
+        let columns: Vec<_> = self
+            .columns
+            .iter()
+            .map(|column| {
+                let info = &column.info;
+                let color = match info.ty {
+                    ColumnType::Int | ColumnType::Float => Color::Cyan,
+                    ColumnType::Date | ColumnType::DateTime => Color::Magenta,
+                    ColumnType::Bool => Color::Yellow,
+                    ColumnType::Text => Color::Reset,
+                    ColumnType::Empty => Color::DarkGray,
+                };
+                let padding = name_width - column.name.width();
+                let mut spans = vec![
+                    Span::raw(format!("{}{}  ", column.name, " ".repeat(padding))),
+                    Span::raw(format!("{:<8}", info.ty.to_string())).fg(color),
+                ];
+                if info.nulls > 0 {
+                    spans.push(
+                        Span::raw(format!("  {} of {} empty", info.nulls, info.rows))
+                            .fg(Color::DarkGray),
+                    );
+                }
+                if let Some(declared) = &column.declared {
+                    spans.push(Span::raw(format!("  ({})", declared)).fg(Color::DarkGray));
+                }
+                ListItem::from(Line::from(spans))
+            })
+            .collect();
+        let highlight_style = (Color::Green, Color::default());
+
+        self.list_state.select(Some(self.selected_column));
+
+        let list = List::new(columns).highlight_style(highlight_style);
+        StatefulWidget::render(list, area, buf, &mut self.list_state);
+    }
+
+    fn render_footer(&self, area: Rect, buf: &mut Buffer) {
+        let info_footer = Paragraph::new("j/k:select enter:jump")
+            .style(Style::new().fg(Color::DarkGray).bg(Color::Rgb(18, 18, 18)))
+            .centered();
+        info_footer.render(area, buf);
+    }
+
+    pub fn select_next(&mut self) {
+        if self.columns.is_empty() {
+            return;
+        }
+        if self.selected_column < self.columns.len() - 1 {
+            self.selected_column += 1;
+        } else {
+            self.selected_column = 0;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if self.columns.is_empty() {
+            return;
+        }
+        if self.selected_column > 0 {
+            self.selected_column -= 1;
+        } else {
+            self.selected_column = self.columns.len() - 1;
+        }
+    }
+
+    /// The index of the selected column.
+    pub fn get_selected(&self) -> Option<usize> {
+        (self.selected_column < self.columns.len()).then_some(self.selected_column)
+    }
+}
+impl Widget for &mut ColumnsView {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        let area = popup_area(area);
+        let title = if self.sampled {
+            format!(
+                "Columns ({}, types from the first rows)",
+                self.columns.len()
+            )
+        } else {
+            format!("Columns ({})", self.columns.len())
+        };
+        let popup = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .title(title);
+        let body_area = popup.inner(area);
+
+        let vertical = Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]);
+        let [columns_area, footer_area] = vertical.areas(body_area);
+
+        Clear.render(area, buf);
+        popup.render(area, buf);
+        self.render_columns(columns_area, buf);
+        self.render_footer(footer_area, buf);
+    }
+}
@@ -0,0 +1,101 @@
+use ratatui::{
+    prelude::*,
+    widgets::{Block, BorderType, Clear, List, ListItem, ListState, Paragraph},
+};
+
+use crate::file::RowError;
+
+use super::finder::popup_area;
+
+#[derive(Default)]
+pub struct DiagnosticsView {
+    errors: Vec<RowError>,
+    list_state: ListState,
+    selected_error: usize,
+}
+impl DiagnosticsView {
+    pub fn set_errors(&mut self, errors: Vec<RowError>) {
+        self.errors = errors;
+        self.selected_error = 0;
+    }
+
+    fn render_errors(&mut self, area: Rect, buf: &mut Buffer) {
+        if self.errors.is_empty() {
+            Line::raw("No malformed rows.")
+                .style(Color::DarkGray)
+                .render(area, buf);
+            return;
+        }
+
+        let errors: Vec<_> = self
+            .errors
+            .iter()
+            .map(|err| {
+                ListItem::from(Line::from(vec![
+                    Span::raw(format!("line {}: ", err.line)).fg(Color::Red),
+                    Span::raw(err.message.as_str()),
+                ]))
+            })
+            .collect();
+        let highlight_style = (Color::Green, Color::default());
+
+        self.list_state.select(Some(self.selected_error));
+
+        let list = List::new(errors).highlight_style(highlight_style);
+        StatefulWidget::render(list, area, buf, &mut self.list_state);
+    }
+
+    fn render_footer(&self, area: Rect, buf: &mut Buffer) {
+        let info_footer = Paragraph::new("j/k:select enter:jump")
+            .style(Style::new().fg(Color::DarkGray).bg(Color::Rgb(18, 18, 18)))
+            .centered();
+        info_footer.render(area, buf);
+    }
+
+    pub fn select_next(&mut self) {
+        if self.errors.is_empty() {
+            return;
+        }
+        if self.selected_error < self.errors.len() - 1 {
+            self.selected_error += 1;
+        } else {
+            self.selected_error = 0;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if self.errors.is_empty() {
+            return;
+        }
+        if self.selected_error > 0 {
+            self.selected_error -= 1;
+        } else {
+            self.selected_error = self.errors.len() - 1;
+        }
+    }
+
+    /// The row index of the selected error.
+    pub fn get_selected(&self) -> Option<usize> {
+        self.errors.get(self.selected_error).map(|err| err.row)
+    }
+}
+impl Widget for &mut DiagnosticsView {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        let area = popup_area(area);
+        let popup = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .title(format!("Malformed rows ({})", self.errors.len()));
+        let body_area = popup.inner(area);
+
+        let vertical = Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]);
+        let [errors_area, footer_area] = vertical.areas(body_area);
+
+        Clear.render(area, buf);
+        popup.render(area, buf);
+        self.render_errors(errors_area, buf);
+        self.render_footer(footer_area, buf);
+    }
+}
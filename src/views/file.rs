@@ -1,11 +1,21 @@
+use std::{
+    path::Path,
+    sync::{atomic::Ordering, mpsc::Sender, Arc},
+    time::Duration,
+};
+
 use ratatui::{
+    crossterm::event::Event,
     prelude::*,
-    widgets::{Block, List, ListItem, ListState, Padding},
+    widgets::{Block, Gauge, List, ListItem, ListState, Padding, Paragraph, Wrap},
 };
 
-use crate::TableFile;
+use crate::{
+    file::{FileInfo, LoadError, Loaded, Progress, Records, RowError, Sheets, TableFile},
+    filter::Filter,
+};
 
-use super::TableView;
+use super::{columns::Column, TableView};
 
 pub struct FileView {
     file: TableFile,
@@ -27,6 +37,14 @@ impl FileView {
         &self.file.name
     }
 
+    pub fn path(&self) -> Option<&Path> {
+        self.file.source.path()
+    }
+
+    pub fn has_error(&self) -> bool {
+        self.file.error.is_some()
+    }
+
     pub fn search_options(&self) -> Vec<String> {
         match &self.file.records {
             None => vec![self.name().to_string()],
@@ -41,25 +59,36 @@ impl FileView {
     }
 
     pub fn select_sheet(&mut self, sheet_idx: usize) {
+        if sheet_idx != self.selected_sheet {
+            self.table_view.reset();
+        }
         self.selected_sheet = sheet_idx;
     }
 
     pub fn next_sheet(&mut self) {
+        if self.file.n_sheets() == 0 {
+            return;
+        }
         if self.selected_sheet < self.file.n_sheets() - 1 {
             self.selected_sheet += 1;
         } else {
             self.selected_sheet = 0;
         }
-        self.try_load_file();
+        self.table_view.reset();
+        self.update_shape();
     }
 
     pub fn previous_sheet(&mut self) {
+        if self.file.n_sheets() == 0 {
+            return;
+        }
         if self.selected_sheet > 0 {
             self.selected_sheet -= 1;
         } else {
             self.selected_sheet = self.file.n_sheets() - 1;
         }
-        self.try_load_file();
+        self.table_view.reset();
+        self.update_shape();
     }
 
     pub fn next_row(&mut self) {
@@ -70,6 +99,138 @@ impl FileView {
         self.table_view.previous_row();
     }
 
+    pub fn select_row(&mut self, idx: usize) {
+        self.table_view.select_row(idx);
+    }
+
+    /// The malformed rows in the current sheet.
+    pub fn errors(&self) -> Vec<RowError> {
+        self.current_records()
+            .map(|records| records.errors().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// The current sheet's columns, and whether their
+    /// types were inferred from only the first rows.
+    pub fn columns(&self) -> (Vec<Column>, bool) {
+        let Some(records) = self.current_records() else {
+            return (vec![], false);
+        };
+        let columns = records
+            .headers
+            .iter()
+            .enumerate()
+            .map(|(i, name)| Column {
+                name: name.to_string(),
+                declared: records
+                    .types
+                    .as_ref()
+                    .and_then(|types| types.get(i))
+                    .filter(|ty| !ty.is_empty())
+                    .map(str::to_string),
+                info: records.columns.get(i).cloned().unwrap_or_default(),
+            })
+            .collect();
+        let sampled = records
+            .columns
+            .first()
+            .is_some_and(|column| column.rows < records.len());
+        (columns, sampled)
+    }
+
+    pub fn selected_column(&self) -> Option<usize> {
+        self.table_view.selected_column()
+    }
+
+    pub fn select_column(&mut self, col: usize) {
+        self.table_view.select_column(col);
+    }
+
+    /// Sort by the selected column, or with `then`,
+    /// by it after any columns already sorted by.
+    pub fn sort(&mut self, then: bool) {
+        self.with_records(|table_view, records| table_view.sort_by_selected(records, then));
+    }
+
+    /// Only show the rows matching the filter expression,
+    /// or all of them if it's empty.
+    pub fn filter(&mut self, expr: &str) -> color_eyre::Result<()> {
+        let Some(records) = self.current_records().cloned() else {
+            return Ok(());
+        };
+        let filter = if expr.trim().is_empty() {
+            None
+        } else {
+            let filter = Filter::parse(expr, &records.headers)?;
+            filter.check(&records)?;
+            Some(filter)
+        };
+        self.table_view.set_filter(&records, filter);
+        Ok(())
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.table_view.is_searching()
+    }
+
+    pub fn has_search(&self) -> bool {
+        self.table_view.has_search()
+    }
+
+    pub fn start_search(&mut self) {
+        self.with_records(|table_view, _| table_view.start_search());
+    }
+
+    pub fn edit_search(&mut self, event: &Event) {
+        self.with_records(|table_view, records| table_view.edit_search(records, event));
+    }
+
+    pub fn cycle_search_mode(&mut self) {
+        self.with_records(|table_view, records| table_view.cycle_search_mode(records));
+    }
+
+    pub fn finish_search(&mut self) {
+        self.with_records(|table_view, records| table_view.finish_search(records));
+    }
+
+    pub fn cancel_search(&mut self) {
+        self.table_view.cancel_search();
+    }
+
+    pub fn clear_search(&mut self) {
+        self.table_view.clear_search();
+    }
+
+    pub fn next_match(&mut self) {
+        self.table_view.next_match();
+    }
+
+    pub fn previous_match(&mut self) {
+        self.table_view.previous_match();
+    }
+
+    /// Take the results of any sort, filter or search
+    /// that has finished going through the rows.
+    pub fn poll_scans(&mut self) {
+        self.with_records(|table_view, records| table_view.poll_scans(records, Duration::ZERO));
+    }
+
+    pub fn is_scanning(&self) -> bool {
+        self.table_view.is_scanning()
+    }
+
+    pub fn cancel_scans(&mut self) {
+        self.with_records(|table_view, records| table_view.cancel_scans(records));
+    }
+
+    pub fn toggle_pin(&mut self) {
+        self.with_records(|table_view, records| table_view.toggle_pin(records));
+    }
+
+    pub fn pin_leading(&mut self) {
+        self.with_records(|table_view, records| table_view.pin_leading(records));
+    }
+
     pub fn next_column(&mut self) {
         self.table_view.next_column();
     }
@@ -78,55 +239,295 @@ impl FileView {
         self.table_view.previous_column();
     }
 
-    pub fn try_load_file(&mut self) {
+    /// Start loading the file if it hasn't been loaded
+    /// (or failed to load) yet.
+    pub fn try_load_file(&mut self, file_idx: usize, tx: &Sender<Loaded>) {
         if self.file.records.is_none() {
-            self.file.load().unwrap();
+            if !self.file.is_loading() && !self.has_error() {
+                self.file.load(file_idx, tx.clone());
+            }
+        } else {
+            self.update_shape();
         }
-        let records = self
-            .file
+    }
+
+    /// Discard any loaded records and load the file again.
+    pub fn reload(&mut self, file_idx: usize, tx: &Sender<Loaded>) {
+        self.cancel_loading();
+        self.file.records = None;
+        self.file.error = None;
+        self.try_load_file(file_idx, tx);
+    }
+
+    /// Load the file again after it changed on disk, showing
+    /// what was loaded before until the new version is ready.
+    /// Files that haven't been loaded are left until they're opened.
+    pub fn refresh(&mut self, file_idx: usize, tx: &Sender<Loaded>) {
+        if self.file.records.is_some() || self.has_error() || self.is_loading() {
+            self.cancel_loading();
+            self.file.error = None;
+            self.file.load(file_idx, tx.clone());
+        }
+    }
+
+    /// Show what was loaded, unless it's from a load
+    /// that has since been cancelled or restarted.
+    pub fn finish_loading(
+        &mut self,
+        progress: &Arc<Progress>,
+        info: FileInfo,
+        result: color_eyre::Result<Sheets>,
+    ) {
+        if !self.file.is_current_load(progress) {
+            return;
+        }
+        let sheet = self.sheet_name().map(str::to_string);
+        self.file.finish_loading(info, result);
+
+        // Stay on the same sheet after a refresh, if it's still there.
+        if let Some(records) = &self.file.records {
+            self.selected_sheet = sheet
+                .and_then(|sheet| records.iter().position(|(name, _)| *name == sheet))
+                .unwrap_or(self.selected_sheet.min(records.len().saturating_sub(1)));
+        }
+        self.with_records(|table_view, records| table_view.reorder(records));
+        self.update_shape();
+    }
+
+    fn sheet_name(&self) -> Option<&str> {
+        self.file
             .records
             .as_ref()
-            .and_then(|records| records.get(self.selected_sheet).map(|(_, recs)| recs));
-        if let Some(records) = records {
-            self.table_view.update_shape(records);
+            .and_then(|records| records.get(self.selected_sheet))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Re-parse the file with the next delimiter.
+    pub fn cycle_delimiter(&mut self, file_idx: usize, tx: &Sender<Loaded>) {
+        self.file.cycle_delimiter();
+        self.reload(file_idx, tx);
+    }
+
+    /// Re-parse the file with or without a header row.
+    pub fn toggle_header(&mut self, file_idx: usize, tx: &Sender<Loaded>) {
+        self.file.toggle_header();
+        self.reload(file_idx, tx);
+    }
+
+    /// Re-parse the file with a sniffed delimiter.
+    pub fn reset_delimiter(&mut self, file_idx: usize, tx: &Sender<Loaded>) {
+        self.file.reset_delimiter();
+        self.reload(file_idx, tx);
+    }
+
+    pub fn cancel_loading(&mut self) {
+        self.file.cancel_loading();
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.file.is_loading()
+    }
+
+    pub fn is_database(&self) -> bool {
+        self.file.is_database()
+    }
+
+    /// Run an SQL query against the database in the
+    /// background and show its result once it's done.
+    pub fn run_query(
+        &mut self,
+        sql: &str,
+        file_idx: usize,
+        tx: &Sender<Loaded>,
+    ) -> color_eyre::Result<()> {
+        self.file.add_query(sql, file_idx, tx.clone())
+    }
+
+    /// Show the result of a query as a new sheet, unless the query
+    /// has since been cancelled. If it failed to run, the error is
+    /// returned, and what was shown before stays.
+    pub fn finish_query(
+        &mut self,
+        progress: &Arc<Progress>,
+        result: color_eyre::Result<Sheets>,
+    ) -> color_eyre::Result<()> {
+        if !self.file.is_current_load(progress) {
+            return Ok(());
         }
+        self.file.finish_query(result)?;
+        self.select_sheet(self.file.n_sheets() - 1);
+        self.update_shape();
+        Ok(())
+    }
+
+    fn current_records(&self) -> Option<&Arc<Records>> {
+        self.file
+            .records
+            .as_ref()
+            .and_then(|records| records.get(self.selected_sheet).map(|(_, recs)| recs))
+    }
+
+    /// Run `f` with the table and the current sheet's records, if they're loaded.
+    fn with_records(&mut self, f: impl FnOnce(&mut TableView, &Arc<Records>)) {
+        if let Some(records) = self.current_records().cloned() {
+            f(&mut self.table_view, &records);
+        }
+    }
+
+    fn update_shape(&mut self) {
+        self.with_records(|table_view, records| table_view.update_shape(records));
+    }
+
+    /// Show how the file was parsed.
+    pub fn render_info(&self, area: Rect, buf: &mut Buffer) {
+        let info = &self.file.info;
+        let encoding = info.encoding.map(|encoding| match info.compression {
+            Some(compression) => format!("{} · {}", encoding.name(), compression),
+            None => encoding.name().to_string(),
+        });
+        let database = self
+            .file
+            .is_database()
+            .then(|| "SQLite · s:query".to_string());
+        let lines: Vec<_> = [
+            info.dialect.map(|dialect| dialect.to_string()),
+            info.fixed_width.as_ref().map(|fw| fw.to_string()),
+            encoding,
+            database,
+        ]
+        .into_iter()
+        .flatten()
+        .map(Line::raw)
+        .collect();
+        Paragraph::new(lines)
+            .style(Color::DarkGray)
+            .centered()
+            .render(area, buf);
     }
 
     pub fn render_sheet_list(&mut self, area: Rect, buf: &mut Buffer) {
         match &self.file.records {
-            None => (),
-            Some(records) => {
-                if records.len() > 1 {
-                    let titles: Vec<_> = records
-                        .iter()
-                        .map(|(name, _)| ListItem::from(name.to_string()))
-                        .collect();
-                    let highlight_style = (Color::Green, Color::default());
-                    let block = Block::new().padding(Padding::horizontal(1));
-
-                    self.list_state.select(Some(self.selected_sheet));
-
-                    let list = List::new(titles)
-                        .block(block)
-                        .highlight_style(highlight_style);
-                    StatefulWidget::render(list, area, buf, &mut self.list_state);
-                }
+            Some(records) if records.len() > 1 => {
+                let titles: Vec<_> = records
+                    .iter()
+                    .map(|(name, _)| ListItem::from(name.to_string()))
+                    .collect();
+                let highlight_style = (Color::Green, Color::default());
+                let block = Block::new().padding(Padding::horizontal(1));
+
+                self.list_state.select(Some(self.selected_sheet));
+
+                let list = List::new(titles)
+                    .block(block)
+                    .highlight_style(highlight_style);
+                StatefulWidget::render(list, area, buf, &mut self.list_state);
             }
+            _ => (),
         }
     }
 }
 impl Widget for &mut FileView {
     fn render(self, area: Rect, buf: &mut Buffer) {
-        let records = self
-            .file
-            .records
-            .as_ref()
-            .and_then(|records| records.get(self.selected_sheet).map(|(_, recs)| recs));
-        match records {
-            None => (),
+        // Anything already loaded is kept on screen while refreshing.
+        if let (Some(progress), None) = (&self.file.progress, &self.file.records) {
+            render_progress(progress, area, buf);
+            return;
+        } else if let Some(err) = &self.file.error {
+            render_error(&self.file, err, area, buf);
+            return;
+        }
+
+        match self.current_records().cloned() {
+            None => {
+                Line::raw("Not loaded. r:reload")
+                    .centered()
+                    .style(Color::DarkGray)
+                    .render(center_line(area), buf);
+            }
             Some(records) => {
-                self.table_view.render(records, area, buf);
+                let loading = self.file.progress.as_deref();
+                self.table_view.render(&records, loading, area, buf);
             }
         }
     }
 }
+
+fn render_progress(progress: &Progress, area: Rect, buf: &mut Buffer) {
+    let total = progress.total_bytes.load(Ordering::Relaxed);
+    let read = progress.bytes_read.load(Ordering::Relaxed);
+    let rows = progress.rows_parsed.load(Ordering::Relaxed);
+    let ratio = if total == 0 {
+        0.
+    } else {
+        (read as f64 / total as f64).min(1.)
+    };
+
+    // The size of piped data isn't known until it's all been read.
+    let label = if total == 0 {
+        format!("{} · {} rows", format_bytes(read), rows)
+    } else {
+        format!(
+            "{} / {} · {} rows",
+            format_bytes(read),
+            format_bytes(total),
+            rows
+        )
+    };
+    let [gauge_area, hint_area] = Layout::vertical([Constraint::Length(1); 2])
+        .flex(layout::Flex::Center)
+        .areas(area);
+    Gauge::default()
+        .gauge_style(Color::Green)
+        .ratio(ratio)
+        .label(label)
+        .render(gauge_area.inner(Margin::new(4, 0)), buf);
+    Line::raw("Loading... esc:cancel")
+        .centered()
+        .style(Color::DarkGray)
+        .render(hint_area, buf);
+}
+
+fn render_error(file: &TableFile, err: &LoadError, area: Rect, buf: &mut Buffer) {
+    let position = match (err.line, err.byte) {
+        (Some(line), Some(byte)) => format!("line {}, byte {}", line, byte),
+        (Some(line), None) => format!("line {}", line),
+        (None, Some(byte)) => format!("byte {}", byte),
+        (None, None) => String::new(),
+    };
+    let lines = vec![
+        Line::raw(format!("Failed to load {}", file.source)).bold(),
+        Line::raw(position),
+        Line::raw(""),
+        Line::raw(err.message.as_str()),
+        Line::raw(""),
+        Line::raw("r:reload").style(Color::DarkGray),
+    ];
+    let height = lines.len() as u16;
+    let [error_area] = Layout::vertical([Constraint::Length(height)])
+        .flex(layout::Flex::Center)
+        .areas(area);
+    Paragraph::new(lines)
+        .style(Color::Red)
+        .centered()
+        .wrap(Wrap { trim: false })
+        .render(error_area.inner(Margin::new(2, 0)), buf);
+}
+
+/// A one-line area at the vertical center of `area`.
+fn center_line(area: Rect) -> Rect {
+    let [line] = Layout::vertical([Constraint::Length(1)])
+        .flex(layout::Flex::Center)
+        .areas(area);
+    line
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024. && unit < UNITS.len() - 1 {
+        size /= 1024.;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
@@ -56,6 +56,9 @@ impl FinderView {
     }
 
     pub fn select_next(&mut self) {
+        if self.results.is_empty() {
+            return;
+        }
         if self.selected_result < self.results.len() - 1 {
             self.selected_result += 1;
         } else {
@@ -64,6 +67,9 @@ impl FinderView {
     }
 
     pub fn select_previous(&mut self) {
+        if self.results.is_empty() {
+            return;
+        }
         if self.selected_result > 0 {
             self.selected_result -= 1;
         } else {
@@ -114,7 +120,7 @@ impl Widget for &mut FinderView {
 }
 
 /// helper function to create a centered rect using up certain percentage of the available rect `r`
-fn popup_area(area: Rect) -> Rect {
+pub(super) fn popup_area(area: Rect) -> Rect {
     let vertical = Layout::vertical([
         Constraint::Fill(1),
         Constraint::Max(24),
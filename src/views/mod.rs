@@ -1,7 +1,16 @@
+mod columns;
+mod diagnostics;
 mod file;
 mod finder;
+mod prompt;
+mod search;
 mod table;
+mod tree;
 
+pub use columns::ColumnsView;
+pub use diagnostics::DiagnosticsView;
 pub use file::FileView;
 pub use finder::FinderView;
+pub use prompt::PromptView;
 pub use table::TableView;
+pub use tree::FileTreeView;
@@ -0,0 +1,100 @@
+use ratatui::{
+    prelude::*,
+    widgets::{Block, BorderType, Borders, Clear, Paragraph, Wrap},
+};
+use tui_input::Input;
+
+use super::finder::popup_area;
+
+/// Prompt for text to act on, such as a filter expression or an SQL
+/// query, showing why it didn't work if it didn't. The text is kept
+/// so that it can be tweaked.
+pub struct PromptView {
+    pub input: Input,
+    error: Option<String>,
+    title: &'static str,
+
+    // An example of what to enter, shown under the input.
+    help: Option<&'static str>,
+    footer: &'static str,
+}
+impl PromptView {
+    pub fn new(title: &'static str, help: Option<&'static str>, footer: &'static str) -> Self {
+        Self {
+            input: Input::default(),
+            error: None,
+            title,
+            help,
+            footer,
+        }
+    }
+
+    pub fn set_error(&mut self, error: String) {
+        self.error = Some(error);
+    }
+
+    pub fn clear_error(&mut self) {
+        self.error = None;
+    }
+
+    pub fn reset(&mut self) {
+        self.input.reset();
+        self.error = None;
+    }
+
+    fn render_footer(&self, area: Rect, buf: &mut Buffer) {
+        let info_footer = Paragraph::new(self.footer)
+            .style(Style::new().fg(Color::DarkGray).bg(Color::Rgb(18, 18, 18)))
+            .centered();
+        info_footer.render(area, buf);
+    }
+}
+impl Widget for &mut PromptView {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        let area = popup_area(area);
+        let popup = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .title(self.title);
+        let body_area = popup.inner(area);
+
+        let help_height = if self.help.is_some() { 2 } else { 0 };
+        let vertical = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(help_height),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+        ]);
+        let [input_area, help_area, error_area, footer_area] = vertical.areas(body_area);
+
+        Clear.render(area, buf);
+        popup.render(area, buf);
+
+        Paragraph::new(self.input.value())
+            .wrap(Wrap { trim: false })
+            .render(input_area, buf);
+
+        if let Some(help) = self.help {
+            Paragraph::new(help)
+                .style(Color::DarkGray)
+                .wrap(Wrap { trim: true })
+                .render(help_area, buf);
+        }
+
+        if let Some(error) = &self.error {
+            let block = Block::new()
+                .borders(Borders::TOP)
+                .border_style(Color::DarkGray)
+                .border_set(symbols::border::PLAIN);
+            Paragraph::new(error.as_str())
+                .style(Color::Red)
+                .wrap(Wrap { trim: true })
+                .block(block)
+                .render(error_area, buf);
+        }
+
+        self.render_footer(footer_area, buf);
+    }
+}
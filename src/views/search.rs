@@ -0,0 +1,41 @@
+use ratatui::prelude::*;
+use tui_input::Input;
+
+use crate::search::SearchMode;
+
+/// The prompt for text to search the table for,
+/// shown in place of the table's footer while typing.
+#[derive(Default)]
+pub struct SearchBar {
+    pub input: Input,
+    pub mode: SearchMode,
+    pub editing: bool,
+    pub error: Option<String>,
+}
+impl SearchBar {
+    pub fn render(&self, area: Rect, buf: &mut Buffer) {
+        let value = self.input.value();
+        let (before, after) = value.split_at(
+            value
+                .char_indices()
+                .nth(self.input.cursor())
+                .map_or(value.len(), |(i, _)| i),
+        );
+        let mut after = after.chars();
+        let cursor = after.next().map_or(" ".to_string(), String::from);
+
+        let mut spans = vec![
+            Span::raw("/").fg(Color::Yellow),
+            Span::raw(before),
+            Span::raw(cursor).reversed(),
+            Span::raw(after.as_str()),
+            Span::raw(format!("  tab:{} enter:done esc:cancel", self.mode)).fg(Color::DarkGray),
+        ];
+        if let Some(error) = &self.error {
+            spans.push(Span::raw(format!("  {}", error)).fg(Color::Red));
+        }
+        Line::from(spans)
+            .style(Style::new().bg(Color::Rgb(18, 18, 18)))
+            .render(area, buf);
+    }
+}
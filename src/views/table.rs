@@ -1,28 +1,740 @@
-use std::iter;
+use std::{borrow::Cow, slice, sync::Arc, time::Duration};
 
 use csv::StringRecord;
-use ratatui::{prelude::*, widgets::*};
+use ratatui::{crossterm::event::Event, prelude::*, widgets::*};
 use unicode_width::UnicodeWidthStr;
 
-use crate::file::Records;
+use tui_input::backend::crossterm::EventHandler;
+
+use crate::{
+    file::{Progress, Records},
+    filter::Filter,
+    scan::Scan,
+    search::{self, Search},
+    sort::{sort_rows, SortKey},
+};
+
+use super::search::SearchBar;
 
 const ITEM_HEIGHT: usize = 1;
 
+/// The width of the bar marking the selected row,
+/// and of the placeholders for columns off screen.
+const HIGHLIGHT_WIDTH: u16 = 3;
+const PLACEHOLDER_WIDTH: u16 = 4;
+
+/// How many rows to sample when sizing columns
+/// for records that are read on demand.
+const WIDTH_SAMPLE_ROWS: usize = 1000;
+
+/// How long to wait for a scan of the rows before showing its
+/// progress, so that quick ones seem to happen at once.
+const SCAN_WAIT: Duration = Duration::from_millis(50);
+
+/// Cells as (row, column) pairs.
+type Cells = Vec<(usize, usize)>;
+
+/// The sort and filter in effect before they were changed.
+type Ordering = (Vec<SortKey>, Option<Arc<Filter>>);
+
+/// The rows being filtered and sorted in the background.
+struct Reorder {
+    scan: Scan<Vec<usize>>,
+
+    // The row (as an index into the records) to keep selected,
+    // and the sort and filter to go back to if it's cancelled,
+    // unless they no longer apply to the records.
+    selected: Option<usize>,
+    previous: Option<Ordering>,
+}
+
 #[derive(Default)]
 pub struct TableView {
     col_widths: Vec<u16>,
     n_rows: usize,
+    complete: bool,
 
-    state: TableState,
-    n_cols: usize,
+    // Only the rows that fit on screen are fetched,
+    // so we track the vertical scroll ourselves rather
+    // than leaving it to the `Table` widget.
+    row_offset: usize,
+    selected_row: Option<usize>,
+
+    // Rows in the order they're shown, as indices
+    // into the records, if they've been sorted or filtered.
+    order: Option<Vec<usize>>,
+    sort: Vec<SortKey>,
+    filter: Option<Arc<Filter>>,
+    reordering: Option<Reorder>,
+
+    // What was dropped from the sort and filter because
+    // its columns went away when the headers changed.
+    notice: Option<String>,
+
+    // Cells matching the search, as (row, column) pairs in the
+    // order they're stored and in the order they're shown, and
+    // where the selection was when the search started, to go back
+    // to if cancelled. Cells are found in the background, and
+    // once they are the first match from there may be jumped to.
+    search_bar: SearchBar,
+    search: Option<Search>,
+    found: Cells,
+    matches: Cells,
+    search_origin: (Option<usize>, usize, Option<usize>),
+    searching: Option<(Scan<Cells>, bool)>,
+
+    // Columns are selected by their index into the records,
+    // with pinned columns kept on the left and the rest
+    // scrolled through, `col_offset` being how many of
+    // those are scrolled past.
+    selected_col: Option<usize>,
+    pinned: Vec<Pin>,
     col_offset: usize,
-    extra_cols: usize,
+
+    state: TableState,
     vertical_scroll_state: ScrollbarState,
 }
+
+/// A column pinned to the left, kept by name as well
+/// to be found again if the headers change.
+struct Pin {
+    col: usize,
+    name: String,
+}
+
+/// A column of the table as drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Slot {
+    Column(usize),
+
+    // Placeholders indicating additional columns to either side.
+    MoreLeft,
+    MoreRight,
+}
 impl TableView {
     pub fn update_shape(&mut self, records: &Records) {
-        self.col_widths = constraint_len_calculator(&records.headers, &records.rows);
-        self.n_rows = records.rows.len();
+        let n_rows = if records.is_lazy() {
+            records.len().min(WIDTH_SAMPLE_ROWS)
+        } else {
+            records.len()
+        };
+        self.col_widths = constraint_len_calculator(&records.headers, &records.window(0..n_rows));
+        if let Some(types) = &records.types {
+            widen(&mut self.col_widths, slice::from_ref(types));
+        }
+        self.n_rows = self.n_shown(records);
+        self.complete = records.is_complete();
+    }
+
+    /// The number of rows shown.
+    fn n_shown(&self, records: &Records) -> usize {
+        self.order.as_ref().map_or(records.len(), Vec::len)
+    }
+
+    /// The index into the records of the row shown at `idx`.
+    fn record_idx(&self, idx: usize) -> usize {
+        self.order
+            .as_ref()
+            .and_then(|order| order.get(idx).copied())
+            .unwrap_or(idx)
+    }
+
+    /// The selected column, as an index into the records' columns.
+    pub fn selected_column(&self) -> Option<usize> {
+        self.selected_col
+    }
+
+    /// Pin the selected column to the left, or unpin it if it's pinned.
+    pub fn toggle_pin(&mut self, records: &Records) {
+        let Some(col) = self.selected_col else {
+            return;
+        };
+        match self.pinned.iter().position(|pin| pin.col == col) {
+            Some(i) => {
+                self.pinned.remove(i);
+            }
+            None => {
+                self.pinned.push(pin(records, col));
+                self.pinned.sort_unstable_by_key(|pin| pin.col);
+            }
+        }
+        self.col_offset = 0;
+    }
+
+    /// Pin the columns up to and including the selected one,
+    /// or unpin them if they're exactly what's pinned.
+    pub fn pin_leading(&mut self, records: &Records) {
+        let Some(col) = self.selected_col else {
+            return;
+        };
+        let is_leading = self.pinned.len() == col + 1
+            && self.pinned.iter().enumerate().all(|(i, pin)| pin.col == i);
+        self.pinned = if is_leading {
+            vec![]
+        } else {
+            (0..=col).map(|col| pin(records, col)).collect()
+        };
+        self.col_offset = 0;
+    }
+
+    fn is_pinned(&self, col: usize) -> bool {
+        self.pinned.iter().any(|pin| pin.col == col)
+    }
+
+    /// The columns in the order they're moved through,
+    /// pinned columns first.
+    fn column_order(&self) -> Vec<usize> {
+        let n_cols = self.col_widths.len();
+        self.pinned
+            .iter()
+            .map(|pin| pin.col)
+            .filter(|col| *col < n_cols)
+            .chain((0..n_cols).filter(|col| !self.is_pinned(*col)))
+            .collect()
+    }
+
+    /// Sort by the selected column, or if it's already sorted by,
+    /// go from ascending to descending to not sorting by it. With
+    /// `then`, the column is added as a further key rather than
+    /// replacing the others.
+    pub fn sort_by_selected(&mut self, records: &Arc<Records>, then: bool) {
+        let Some(col) = self.selected_column() else {
+            return;
+        };
+        let previous = self.applied_ordering();
+        self.notice = None;
+        match self.sort.iter().position(|key| key.col == col) {
+            Some(i) if then || self.sort.len() == 1 => {
+                if self.sort[i].descending {
+                    self.sort.remove(i);
+                } else {
+                    self.sort[i].descending = true;
+                }
+            }
+            _ => {
+                let key = SortKey {
+                    col,
+                    name: records.headers.get(col).unwrap_or_default().to_string(),
+                    descending: false,
+                };
+                if then {
+                    self.sort.push(key);
+                } else {
+                    self.sort = vec![key];
+                }
+            }
+        }
+        self.start_reorder(records, previous);
+    }
+
+    /// Only show the rows matching the filter, or all of them if `None`.
+    pub fn set_filter(&mut self, records: &Arc<Records>, filter: Option<Filter>) {
+        let previous = self.applied_ordering();
+        self.filter = filter.map(Arc::new);
+        self.notice = None;
+        self.start_reorder(records, previous);
+    }
+
+    /// Go back to showing all the rows in their original order,
+    /// without any search or pinned columns.
+    pub fn reset(&mut self) {
+        self.sort.clear();
+        self.filter = None;
+        self.reordering = None;
+        self.notice = None;
+        self.order = None;
+        self.pinned.clear();
+        self.col_offset = 0;
+        self.clear_search();
+    }
+
+    /// Filter, sort and search the rows again after they've been
+    /// reloaded, keeping the same row selected.
+    pub fn reorder(&mut self, records: &Arc<Records>) {
+        self.resolve_columns(records);
+
+        // What's shown until the rows have been gone through
+        // again mustn't point past the end of them.
+        if let Some(order) = &mut self.order {
+            order.retain(|idx| *idx < records.len());
+        }
+        self.found.retain(|(idx, _)| *idx < records.len());
+        self.n_rows = self.n_shown(records);
+        self.start_reorder(records, None);
+        self.start_finding(records, false);
+    }
+
+    /// The sort and filter that the rows shown are in,
+    /// which are changing if they're being reordered.
+    fn applied_ordering(&self) -> Option<Ordering> {
+        match &self.reordering {
+            Some(reorder) => reorder.previous.clone(),
+            None => Some((self.sort.clone(), self.filter.clone())),
+        }
+    }
+
+    /// Start filtering and sorting the rows in the background.
+    fn start_reorder(&mut self, records: &Arc<Records>, previous: Option<Ordering>) {
+        let selected = self.selected_row.map(|idx| self.record_idx(idx));
+        self.reordering = None;
+        if self.filter.is_none() && self.sort.is_empty() {
+            self.set_order(records, None, selected);
+            return;
+        }
+
+        let filter = self.filter.clone();
+        let keys = self.sort.clone();
+        let scan = Scan::start(records, move |records, progress| {
+            let mut rows = match &filter {
+                Some(filter) => {
+                    let mut rows = vec![];
+                    records.for_each(progress, |idx, row| {
+                        if filter.matches(row) {
+                            rows.push(idx);
+                        }
+                    })?;
+                    rows
+                }
+                None => (0..records.len()).collect(),
+            };
+            sort_rows(records, &mut rows, &keys, progress)?;
+            Ok(rows)
+        });
+        self.reordering = Some(Reorder {
+            scan,
+            selected,
+            previous,
+        });
+        self.poll_scans(records, SCAN_WAIT);
+    }
+
+    fn set_order(&mut self, records: &Records, order: Option<Vec<usize>>, selected: Option<usize>) {
+        self.order = order;
+        self.n_rows = self.n_shown(records);
+        if let Some(selected) = selected {
+            self.select_row(selected);
+        }
+        self.match_found();
+    }
+
+    /// Take the results of any scans of the rows that have finished,
+    /// waiting up to `timeout` for them.
+    pub fn poll_scans(&mut self, records: &Records, timeout: Duration) {
+        if let Some(result) = self
+            .reordering
+            .as_ref()
+            .and_then(|reorder| reorder.scan.result(timeout))
+        {
+            let reorder = self.reordering.take().unwrap();
+            match result {
+                Ok(order) => self.set_order(records, Some(order), reorder.selected),
+                Err(err) => {
+                    self.restore_ordering(records, reorder.previous);
+                    self.notice = Some(format!("Couldn't sort or filter: {}", err));
+                }
+            }
+        }
+        if let Some(result) = self
+            .searching
+            .as_ref()
+            .and_then(|(scan, _)| scan.result(timeout))
+        {
+            let (_, jump) = self.searching.take().unwrap();
+            match result {
+                Ok(found) => {
+                    self.found = found;
+                    self.match_found();
+                    if jump {
+                        self.jump_to_match();
+                    }
+                }
+                Err(err) => {
+                    self.clear_search();
+                    self.notice = Some(format!("Couldn't search: {}", err));
+                }
+            }
+        }
+    }
+
+    /// What the rows are being gone through for, and how far along.
+    fn scan_progress(&self) -> Option<(&str, f64)> {
+        if let Some(reorder) = &self.reordering {
+            let doing = if self.filter.is_some() {
+                "Filtering"
+            } else {
+                "Sorting"
+            };
+            Some((doing, reorder.scan.ratio()))
+        } else {
+            self.searching
+                .as_ref()
+                .map(|(scan, _)| ("Searching", scan.ratio()))
+        }
+    }
+
+    pub fn is_scanning(&self) -> bool {
+        self.reordering.is_some() || self.searching.is_some()
+    }
+
+    /// Stop sorting, filtering or searching the rows, going back to
+    /// how they were shown before if possible, or else to showing
+    /// them all in their original order.
+    pub fn cancel_scans(&mut self, records: &Records) {
+        if let Some(reorder) = self.reordering.take() {
+            self.restore_ordering(records, reorder.previous);
+        }
+        if self.searching.is_some() {
+            self.clear_search();
+        }
+    }
+
+    fn restore_ordering(&mut self, records: &Records, previous: Option<Ordering>) {
+        match previous {
+            Some((sort, filter)) => {
+                self.sort = sort;
+                self.filter = filter;
+            }
+            None => {
+                self.sort.clear();
+                self.filter = None;
+                self.set_order(records, None, None);
+            }
+        }
+    }
+
+    /// Find the sorted, filtered and pinned columns again by name, in
+    /// case the headers changed, dropping those that aren't there anymore.
+    fn resolve_columns(&mut self, records: &Records) {
+        let headers = &records.headers;
+        self.pinned.retain_mut(|pin| {
+            if headers.get(pin.col) == Some(pin.name.as_str()) {
+                return true;
+            }
+            match headers.iter().position(|name| name == pin.name) {
+                Some(col) => {
+                    pin.col = col;
+                    true
+                }
+                None => false,
+            }
+        });
+        self.pinned.sort_unstable_by_key(|pin| pin.col);
+        self.pinned.dedup_by_key(|pin| pin.col);
+
+        let mut dropped = vec![];
+        self.sort.retain_mut(|key| {
+            if headers.get(key.col) == Some(key.name.as_str()) {
+                return true;
+            }
+            match headers.iter().position(|name| name == key.name) {
+                Some(col) => {
+                    key.col = col;
+                    true
+                }
+                None => {
+                    dropped.push(format!("sort by `{}`", key.name));
+                    false
+                }
+            }
+        });
+        if let Some(filter) = self.filter.take() {
+            let resolved = Filter::parse(filter.source(), headers)
+                .and_then(|resolved| resolved.check(records).map(|_| resolved));
+            match resolved {
+                Ok(resolved) => self.filter = Some(Arc::new(resolved)),
+                Err(_) => dropped.push(format!("filter `{}`", filter.source())),
+            }
+        }
+        if !dropped.is_empty() {
+            self.notice = Some(format!("Dropped {}", dropped.join(" and ")));
+        }
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.search_bar.editing
+    }
+
+    pub fn has_search(&self) -> bool {
+        self.search.is_some()
+    }
+
+    /// Start typing text to search for, starting afresh.
+    pub fn start_search(&mut self) {
+        self.search_origin = (self.selected_row, self.col_offset, self.selected_col);
+        self.search_bar.input.reset();
+        self.search_bar.error = None;
+        self.search_bar.editing = true;
+        self.search = None;
+        self.searching = None;
+        self.found.clear();
+        self.matches.clear();
+    }
+
+    /// Edit the search text, jumping to the first match
+    /// from where the search started. Records read on demand
+    /// are only searched when done typing, as that's slow.
+    pub fn edit_search(&mut self, records: &Arc<Records>, event: &Event) {
+        if self.search_bar.input.handle_event(event).is_some() && !records.is_lazy() {
+            self.update_search(records);
+        }
+    }
+
+    pub fn cycle_search_mode(&mut self, records: &Arc<Records>) {
+        self.search_bar.mode = self.search_bar.mode.next();
+        if !records.is_lazy() {
+            self.update_search(records);
+        }
+    }
+
+    /// Stop typing, keeping the matches highlighted.
+    pub fn finish_search(&mut self, records: &Arc<Records>) {
+        if records.is_lazy() {
+            self.update_search(records);
+        }
+        self.search_bar.editing = false;
+        if self.search_bar.error.is_some() {
+            self.clear_search();
+        }
+    }
+
+    /// Stop typing and go back to where the search started.
+    pub fn cancel_search(&mut self) {
+        self.restore_search_origin();
+        self.clear_search();
+    }
+
+    pub fn clear_search(&mut self) {
+        self.search_bar.editing = false;
+        self.search = None;
+        self.searching = None;
+        self.found.clear();
+        self.matches.clear();
+    }
+
+    fn restore_search_origin(&mut self) {
+        let (row, col_offset, col) = self.search_origin;
+        self.selected_row = row;
+        self.col_offset = col_offset;
+        self.selected_col = col;
+    }
+
+    fn update_search(&mut self, records: &Arc<Records>) {
+        let query = self.search_bar.input.value();
+        self.search_bar.error = None;
+        self.search = if query.is_empty() {
+            None
+        } else {
+            match Search::new(query, self.search_bar.mode) {
+                Ok(search) => Some(search),
+                Err(err) => {
+                    self.search_bar.error = Some(err.to_string());
+                    None
+                }
+            }
+        };
+        self.restore_search_origin();
+        self.start_finding(records, true);
+    }
+
+    /// Start finding the cells matching the search in the background,
+    /// jumping to the first match from the selected cell once they're
+    /// found if `jump` is set.
+    fn start_finding(&mut self, records: &Arc<Records>, jump: bool) {
+        let Some(search) = self.search.clone() else {
+            self.searching = None;
+            self.found.clear();
+            self.matches.clear();
+            return;
+        };
+        let scan = Scan::start(records, move |records, progress| {
+            search.find_cells(records, progress)
+        });
+        self.searching = Some((scan, jump));
+        self.poll_scans(records, SCAN_WAIT);
+    }
+
+    /// Put the cells found by the search in the order the rows are
+    /// shown, leaving out those in rows that are filtered out.
+    fn match_found(&mut self) {
+        self.matches = match &self.order {
+            Some(order) => {
+                let n_records = order.iter().max().map_or(0, |idx| idx + 1);
+                let mut positions = vec![None; n_records];
+                for (i, idx) in order.iter().enumerate() {
+                    positions[*idx] = Some(i);
+                }
+                let mut matches: Vec<_> = self
+                    .found
+                    .iter()
+                    .filter_map(|&(idx, col)| Some((*positions.get(idx)?.as_ref()?, col)))
+                    .collect();
+                matches.sort_unstable();
+                matches
+            }
+            None => self.found.clone(),
+        };
+    }
+
+    /// Select the first match from the selected cell, wrapping around.
+    fn jump_to_match(&mut self) {
+        let cursor = self.cursor();
+        if let Some(&(row, col)) = self
+            .matches
+            .iter()
+            .find(|cell| **cell >= cursor)
+            .or(self.matches.first())
+        {
+            self.select_cell(row, col);
+        }
+    }
+
+    /// The selected row and column.
+    fn cursor(&self) -> (usize, usize) {
+        (
+            self.selected_row.unwrap_or_default(),
+            self.selected_col.unwrap_or_default(),
+        )
+    }
+
+    /// Select the match after the selected cell, wrapping around.
+    pub fn next_match(&mut self) {
+        if let Some((row, col)) = search::next_match(&self.matches, self.cursor()) {
+            self.select_cell(row, col);
+        }
+    }
+
+    pub fn previous_match(&mut self) {
+        if let Some((row, col)) = search::previous_match(&self.matches, self.cursor()) {
+            self.select_cell(row, col);
+        }
+    }
+
+    fn select_cell(&mut self, row: usize, col: usize) {
+        self.selected_row = Some(row);
+        self.select_column(col);
+    }
+
+    /// Select the column at `col` in the records.
+    /// It's scrolled to when next drawn.
+    pub fn select_column(&mut self, col: usize) {
+        self.selected_col = Some(col);
+    }
+
+    /// The column's name, with the direction
+    /// and precedence of any sort on it.
+    fn header_label(&self, col: usize, name: &str) -> String {
+        match self.sort.iter().position(|key| key.col == col) {
+            Some(i) => {
+                let arrow = if self.sort[i].descending {
+                    "▼"
+                } else {
+                    "▲"
+                };
+                if self.sort.len() > 1 {
+                    format!("{} {}{}", name, arrow, i + 1)
+                } else {
+                    format!("{} {}", name, arrow)
+                }
+            }
+            None => name.to_string(),
+        }
+    }
+
+    /// Scroll so that the selected row is visible
+    /// and return the range of rows that fit in `height`.
+    fn visible_rows(&mut self, height: usize) -> std::ops::Range<usize> {
+        if let Some(selected) = self.selected_row {
+            let selected = selected.min(self.n_rows.saturating_sub(1));
+            self.selected_row = Some(selected);
+            if selected < self.row_offset {
+                self.row_offset = selected;
+            } else if height > 0 && selected >= self.row_offset + height {
+                self.row_offset = selected + 1 - height;
+            }
+        }
+        let start = self.row_offset.min(self.n_rows);
+        let end = (start + height).min(self.n_rows);
+        start..end
+    }
+
+    /// Scroll so that the selected column is visible and return
+    /// the columns that fit in `width`, pinned columns first.
+    fn visible_columns(&mut self, width: u16) -> Vec<Slot> {
+        let n_cols = self.col_widths.len();
+        if let Some(selected) = self.selected_col {
+            self.selected_col = n_cols.checked_sub(1).map(|last| selected.min(last));
+        }
+        let scrollable: Vec<_> = (0..n_cols).filter(|col| !self.is_pinned(*col)).collect();
+        let pinned: Vec<_> = self
+            .pinned
+            .iter()
+            .map(|pin| pin.col)
+            .filter(|col| *col < n_cols)
+            .collect();
+        // Each column takes its padding and the spacing between columns
+        // as well, and the placeholders for more columns to either side
+        // take the same as a column of width 2.
+        let col_width = |col: usize| self.col_widths[col] + 2;
+        let pinned_width: u16 = pinned.iter().map(|col| col_width(*col)).sum();
+        let width = width
+            .saturating_sub(HIGHLIGHT_WIDTH)
+            .saturating_sub(pinned_width);
+
+        // How many of the scrollable columns fit from `offset` on.
+        // WARN: This can be improved as if we have e.g. a screen
+        // width of 10 and a column of width >10 then nothing
+        // shows up; in that case we should truncate that column to fit.
+        let n_fit = |offset: usize| {
+            let fit = |width: u16| {
+                let mut fit_cols = 0;
+                let mut n_fit = 0;
+                for col in &scrollable[offset..] {
+                    if fit_cols + col_width(*col) > width {
+                        break;
+                    }
+                    n_fit += 1;
+                    fit_cols += col_width(*col);
+                }
+                n_fit
+            };
+            let width = if offset > 0 {
+                width.saturating_sub(PLACEHOLDER_WIDTH)
+            } else {
+                width
+            };
+            match fit(width) {
+                n_fit if offset + n_fit < scrollable.len() => {
+                    fit(width.saturating_sub(PLACEHOLDER_WIDTH))
+                }
+                n_fit => n_fit,
+            }
+        };
+
+        self.col_offset = self.col_offset.min(scrollable.len().saturating_sub(1));
+        let selected = self
+            .selected_col
+            .and_then(|col| scrollable.iter().position(|c| *c == col));
+        if let Some(selected) = selected {
+            if selected < self.col_offset {
+                self.col_offset = selected;
+            }
+            while self.col_offset < selected && selected >= self.col_offset + n_fit(self.col_offset)
+            {
+                self.col_offset += 1;
+            }
+        }
+        let start = self.col_offset.min(scrollable.len());
+        let end = start + n_fit(start);
+
+        let mut slots: Vec<_> = pinned.into_iter().map(Slot::Column).collect();
+        if start > 0 {
+            slots.push(Slot::MoreLeft);
+        }
+        slots.extend(scrollable[start..end].iter().copied().map(Slot::Column));
+        if end < scrollable.len() {
+            slots.push(Slot::MoreRight);
+        }
+        slots
     }
 
     fn render_table(&mut self, records: &Records, area: Rect, buf: &mut Buffer) {
@@ -35,85 +747,105 @@ impl TableView {
             .add_modifier(Modifier::REVERSED)
             .fg(Color::Red);
 
-        // Figure out how many columns we can display on scren.
-        // WARN: This can be improved as if we have e.g. a screen
-        // width of 10 and a column of width >10 then nothing
-        // shows up; in that case we should truncate that column to fit.
-        let mut fit_cols = 0;
-        let mut n_cols = 0;
-        let start_idx = self.col_offset;
-        for col_width in self.col_widths.iter().skip(start_idx) {
-            if fit_cols + col_width > area.width {
-                break;
-            } else {
-                n_cols += 1;
-                fit_cols += col_width;
-            }
-        }
-        let total_cols = self.col_widths.len();
-        let extra_cols = total_cols - (start_idx + n_cols);
-        self.n_cols = n_cols;
-        self.extra_cols = extra_cols;
-
-        // Placeholder indicating additional columns to the left.
-        // the `take(extra_cols)` bit ensures that if there are no
-        // extra columns this will be an empty iterator.
-        let extra_col_left =
-            iter::once(Cell::from(Text::from("<<").alignment(Alignment::Left))).take(start_idx);
-
-        // Placeholder indicating additional columns to the right.
-        // the `take(extra_cols)` bit ensures that if there are no
-        // extra columns this will be an empty iterator.
-        let extra_col_right =
-            iter::once(Cell::from(Text::from(">>").alignment(Alignment::Right))).take(extra_cols);
-
-        let header = extra_col_left
-            .clone()
-            .chain(
-                records
-                    .headers
-                    .iter()
-                    .skip(start_idx)
-                    .take(n_cols)
-                    .map(Cell::from),
-            )
-            .chain(extra_col_right.clone())
+        // Records that are still being indexed grow between frames.
+        self.n_rows = self.n_shown(records);
+        self.complete = records.is_complete();
+
+        // The header takes up a line, plus one for the types if known.
+        let header_height = if records.types.is_some() { 2 } else { 1 };
+        let visible = self.visible_rows(area.height.saturating_sub(header_height) as usize);
+        let window = match &self.order {
+            Some(order) => Cow::Owned(records.rows_at(&order[visible.clone()])),
+            None => records.window(visible.clone()),
+        };
+
+        // Decimal points are lined up, which can make cells wider.
+        let window: Vec<StringRecord> = window
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .enumerate()
+                    .map(|(i, cell)| match records.columns.get(i) {
+                        Some(column) => column.align(cell),
+                        None => Cow::Borrowed(cell),
+                    })
+                    .collect()
+            })
+            .collect();
+        let headers: StringRecord = records
+            .headers
+            .iter()
+            .enumerate()
+            .map(|(i, name)| self.header_label(i, name))
+            .collect();
+        widen(&mut self.col_widths, slice::from_ref(&headers));
+        widen(&mut self.col_widths, &window);
+
+        let slots = self.visible_columns(area.width);
+
+        // Numbers are right-aligned and everything else left-aligned.
+        let alignment = |i: usize| match records.columns.get(i) {
+            Some(column) if column.ty.is_numeric() => Alignment::Right,
+            _ => Alignment::Left,
+        };
+
+        let placeholder = |slot: Slot| match slot {
+            Slot::MoreLeft => Cell::from(Text::from("<<").alignment(Alignment::Left)),
+            _ => Cell::from(Text::from(">>").alignment(Alignment::Right)),
+        };
+        let header = slots
+            .iter()
+            .map(|slot| {
+                let Slot::Column(i) = *slot else {
+                    return placeholder(*slot);
+                };
+                let name = headers.get(i).unwrap_or_default();
+                let ty = records.types.as_ref().and_then(|types| types.get(i));
+                let mut lines = vec![if self.is_pinned(i) {
+                    Line::raw(name).fg(Color::Cyan)
+                } else {
+                    Line::raw(name)
+                }];
+                lines.extend(ty.map(|ty| Line::raw(ty).fg(Color::DarkGray)));
+                Cell::from(Text::from(lines).alignment(alignment(i)))
+            })
             .collect::<Row>()
             .style(header_style)
-            .height(1);
-        let rows = records.rows.iter().enumerate().map(|(i, data)| {
-            let color = match i % 2 {
-                0 => Color::Reset,
-                _ => Color::Rgb(32, 32, 32),
+            .height(header_height);
+        let rows = window.iter().enumerate().map(|(i, data)| {
+            let i = visible.start + i;
+            let color = if records.is_flagged(self.record_idx(i)) {
+                Color::Rgb(96, 24, 24)
+            } else {
+                match i % 2 {
+                    0 => Color::Reset,
+                    _ => Color::Rgb(32, 32, 32),
+                }
             };
-            extra_col_left
-                .clone()
-                .chain(
-                    data.iter()
-                        .skip(start_idx)
-                        .take(n_cols)
-                        .map(|val| Cell::from(Text::from(val).alignment(Alignment::Right))),
-                )
-                .chain(extra_col_right.clone())
+            slots
+                .iter()
+                .map(|slot| {
+                    let Slot::Column(i) = *slot else {
+                        return placeholder(*slot);
+                    };
+                    let val = data.get(i).unwrap_or_default();
+                    let line = if val.trim().is_empty() {
+                        Line::raw("∅").fg(Color::DarkGray)
+                    } else {
+                        highlight(val, self.search.as_ref())
+                    };
+                    Cell::from(Text::from(line).alignment(alignment(i)))
+                })
                 .collect::<Row>()
                 .style(Style::new().fg(Color::Reset).bg(color))
         });
         let bar = " █ ";
 
-        let scroll_indication_left = iter::once(Constraint::Min(1)).take(start_idx);
-        let scroll_indication_right = iter::once(Constraint::Min(1)).take(extra_cols);
-        let widths = scroll_indication_left
-            .chain(
-                self.col_widths
-                    .iter()
-                    .skip(start_idx)
-                    .take(n_cols)
-                    .map(|width| {
-                        // + 1 is for padding.
-                        Constraint::Length(width + 1)
-                    }),
-            )
-            .chain(scroll_indication_right);
+        let widths = slots.iter().map(|slot| match slot {
+            // + 1 is for padding.
+            Slot::Column(i) => Constraint::Length(self.col_widths[*i] + 1),
+            _ => Constraint::Min(1),
+        });
 
         let t = Table::new(rows, widths)
             .header(header)
@@ -128,10 +860,24 @@ impl TableView {
             ]))
             .bg(Color::Reset)
             .highlight_spacing(HighlightSpacing::Always);
+
+        // The table only holds the visible rows,
+        // so the selection is relative to those.
+        *self.state.offset_mut() = 0;
+        self.state
+            .select(self.selected_row.map(|i| i.saturating_sub(visible.start)));
+        self.state.select_column(
+            self.selected_col
+                .and_then(|col| slots.iter().position(|slot| *slot == Slot::Column(col))),
+        );
         StatefulWidget::render(t, area, buf, &mut self.state);
     }
 
     fn render_scrollbar(&mut self, area: Rect, buf: &mut Buffer) {
+        self.vertical_scroll_state = self
+            .vertical_scroll_state
+            .content_length(self.n_rows * ITEM_HEIGHT)
+            .position(self.selected_row.unwrap_or_default() * ITEM_HEIGHT);
         Scrollbar::default()
             .orientation(ScrollbarOrientation::VerticalRight)
             .begin_symbol(None)
@@ -146,39 +892,128 @@ impl TableView {
             );
     }
 
-    fn render_footer(&self, area: Rect, buf: &mut Buffer) {
-        let info_footer = Paragraph::new("j/k:row h/l:col m:maximize ;:find sheet")
-            .style(Style::new().fg(Color::DarkGray).bg(Color::Rgb(18, 18, 18)))
-            .centered();
-        info_footer.render(area, buf);
+    fn render_footer(
+        &self,
+        records: &Records,
+        loading: Option<&Progress>,
+        area: Rect,
+        buf: &mut Buffer,
+    ) {
+        let style = Style::new().fg(Color::DarkGray).bg(Color::Rgb(18, 18, 18));
+        if self.search_bar.editing {
+            self.search_bar.render(area, buf);
+        } else if loading.is_some() {
+            // Shown while the file is loaded again or a query is run.
+            Paragraph::new("Loading... esc:cancel")
+                .style(style)
+                .centered()
+                .render(area, buf);
+        } else if let Some((doing, ratio)) = self.scan_progress() {
+            Paragraph::new(format!("{}... {:.0}% esc:cancel", doing, ratio * 100.))
+                .style(style)
+                .centered()
+                .render(area, buf);
+        } else {
+            let hints = if self.search.is_some() {
+                "n/N:match esc:clear o/O:sort f:filter m:maximize"
+            } else {
+                "j/k:row h/l:col p/P:pin o/O:sort f:filter /:search i:columns m:maximize ;:find sheet e:errors"
+            };
+            Paragraph::new(hints)
+                .style(style)
+                .centered()
+                .render(area, buf);
+        }
+
+        // Trailing "+" means more rows are still being indexed.
+        let more = if self.complete { "" } else { "+" };
+        let n_errors = records.n_errors();
+        let mut status = vec![];
+        if let Some(notice) = &self.notice {
+            status.push(Span::raw(notice.as_str()).fg(Color::Yellow));
+            status.push(Span::raw(" · "));
+        }
+        if self.search.is_some() {
+            let matches = search::describe_matches(&self.matches, self.cursor());
+            status.push(Span::raw(matches).fg(Color::Yellow));
+            status.push(Span::raw(" · "));
+        }
+        if let Some(err) = records.read_error() {
+            status.push(Span::raw(format!("couldn't read rows: {}", err)).fg(Color::Red));
+            status.push(Span::raw(" · "));
+        }
+        if n_errors > 0 {
+            status.push(Span::raw(format!("{}{} errors", n_errors, more)).fg(Color::Red));
+            status.push(Span::raw(" · "));
+        }
+        if self.filter.is_some() {
+            status.push(Span::raw(format!(
+                "showing {} of {}{} rows ",
+                self.n_rows,
+                records.len(),
+                more
+            )));
+        } else {
+            status.push(Span::raw(format!("{}{} rows ", self.n_rows, more)));
+        }
+        Line::from(status)
+            .style(style)
+            .right_aligned()
+            .render(area, buf);
     }
 
-    pub fn render(&mut self, records: &Records, area: Rect, buf: &mut Buffer) {
+    pub fn render(
+        &mut self,
+        records: &Records,
+        loading: Option<&Progress>,
+        area: Rect,
+        buf: &mut Buffer,
+    ) {
         let vertical = &Layout::vertical([Constraint::Min(5), Constraint::Length(1)]);
         let rects = vertical.split(area);
 
         self.render_table(records, rects[0], buf);
         self.render_scrollbar(rects[0], buf);
-        self.render_footer(rects[1], buf);
+        self.render_footer(records, loading, rects[1], buf);
+    }
+
+    /// Select the row at `idx` in the records, wherever it's shown.
+    /// Past the last row, e.g. where reading stopped, the last
+    /// row is selected.
+    pub fn select_row(&mut self, idx: usize) {
+        self.selected_row = match &self.order {
+            Some(order) => order.iter().position(|row| *row == idx),
+            None => Some(idx.min(self.n_rows.saturating_sub(1))),
+        };
     }
 
     pub fn next_row(&mut self) {
-        let i = match self.state.selected() {
+        if self.n_rows == 0 {
+            return;
+        }
+        let i = match self.selected_row {
             Some(i) => {
                 if i >= self.n_rows - 1 {
-                    0
+                    // Don't wrap around while there may be more rows to come.
+                    if self.complete {
+                        0
+                    } else {
+                        i
+                    }
                 } else {
                     i + 1
                 }
             }
             None => 0,
         };
-        self.state.select(Some(i));
-        self.vertical_scroll_state = self.vertical_scroll_state.position(i * ITEM_HEIGHT);
+        self.selected_row = Some(i);
     }
 
     pub fn previous_row(&mut self) {
-        let i = match self.state.selected() {
+        if self.n_rows == 0 {
+            return;
+        }
+        let i = match self.selected_row {
             Some(i) => {
                 if i == 0 {
                     self.n_rows - 1
@@ -188,39 +1023,56 @@ impl TableView {
             }
             None => 0,
         };
-        self.state.select(Some(i));
-        self.vertical_scroll_state = self.vertical_scroll_state.position(i * ITEM_HEIGHT);
+        self.selected_row = Some(i);
     }
 
     pub fn next_column(&mut self) {
-        match self.state.selected_column() {
-            None => {
-                self.state.select_next_column();
-            }
-            Some(idx) => {
-                if idx >= self.n_cols && self.extra_cols > 0 {
-                    self.col_offset += 1;
-                } else {
-                    self.state.select_next_column();
-                }
-            }
-        }
+        let order = self.column_order();
+        self.selected_col = match self.selected_col {
+            Some(col) => order
+                .iter()
+                .position(|c| *c == col)
+                .and_then(|i| order.get(i + 1))
+                .or(order.last())
+                .copied(),
+            None => order.first().copied(),
+        };
     }
 
     pub fn previous_column(&mut self) {
-        match self.state.selected_column() {
-            None => {
-                self.state.select_previous_column();
-            }
-            Some(idx) => {
-                if idx <= 1 && self.col_offset > 0 {
-                    self.col_offset -= 1;
-                } else {
-                    self.state.select_previous_column();
-                }
-            }
-        }
+        let order = self.column_order();
+        self.selected_col = match self.selected_col {
+            Some(col) => order
+                .iter()
+                .position(|c| *c == col)
+                .map(|i| order[i.saturating_sub(1)]),
+            None => order.first().copied(),
+        };
+    }
+}
+
+fn pin(records: &Records, col: usize) -> Pin {
+    Pin {
+        col,
+        name: records.headers.get(col).unwrap_or_default().to_string(),
+    }
+}
+
+/// The cell's text, with any matches of the search highlighted.
+fn highlight<'a>(cell: &'a str, search: Option<&Search>) -> Line<'a> {
+    let Some(search) = search else {
+        return Line::raw(cell);
+    };
+    let style = Style::new().fg(Color::Black).bg(Color::Yellow);
+    let mut spans = vec![];
+    let mut end = 0;
+    for range in search.find(cell) {
+        spans.push(Span::raw(&cell[end..range.start]));
+        spans.push(Span::styled(&cell[range.clone()], style));
+        end = range.end;
     }
+    spans.push(Span::raw(&cell[end..]));
+    Line::from(spans)
 }
 
 fn constraint_len_calculator(cols: &StringRecord, items: &[StringRecord]) -> Vec<u16> {
@@ -228,10 +1080,162 @@ fn constraint_len_calculator(cols: &StringRecord, items: &[StringRecord]) -> Vec
         .iter()
         .map(|col| UnicodeWidthStr::width(col) as u16)
         .collect();
+    widen(&mut max_lens, items);
+    max_lens
+}
+
+/// Widen columns as needed to fit the provided rows.
+fn widen(max_lens: &mut Vec<u16>, items: &[StringRecord]) {
     for row in items {
         for (i, value) in row.iter().enumerate() {
-            max_lens[i] = max_lens[i].max(UnicodeWidthStr::width(value) as u16);
+            let width = UnicodeWidthStr::width(value) as u16;
+            match max_lens.get_mut(i) {
+                Some(max_len) => *max_len = (*max_len).max(width),
+                None => max_lens.push(width),
+            }
         }
     }
-    max_lens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::Rows;
+
+    /// Records with a row of three-letter cells,
+    /// so that each column is as wide as the next.
+    fn records(headers: &[&str]) -> Arc<Records> {
+        Arc::new(Records {
+            headers: StringRecord::from(headers.to_vec()),
+            types: None,
+            columns: vec![],
+            rows: Rows::Loaded(vec![StringRecord::from(vec!["xxx"; headers.len()])]),
+            errors: vec![],
+        })
+    }
+
+    fn table(records: &Records, selected_col: usize) -> TableView {
+        let mut table = TableView::default();
+        table.update_shape(records);
+        table.selected_col = Some(selected_col);
+        table
+    }
+
+    fn pinned(table: &TableView) -> Vec<usize> {
+        table.pinned.iter().map(|pin| pin.col).collect()
+    }
+
+    #[test]
+    fn pins_the_selected_column() {
+        let records = records(&["a", "b", "c", "d", "e"]);
+        let mut table = table(&records, 2);
+
+        table.toggle_pin(&records);
+        assert_eq!(table.column_order(), [2, 0, 1, 3, 4]);
+        table.selected_col = Some(4);
+        table.toggle_pin(&records);
+        assert_eq!(table.column_order(), [2, 4, 0, 1, 3]);
+        table.selected_col = Some(2);
+        table.toggle_pin(&records);
+        assert_eq!(table.column_order(), [4, 0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn toggles_pinning_the_leading_columns() {
+        let records = records(&["a", "b", "c", "d"]);
+        let mut table = table(&records, 3);
+        table.toggle_pin(&records);
+
+        // Whatever else is pinned is replaced, and pressing
+        // it again unpins them.
+        table.selected_col = Some(1);
+        table.pin_leading(&records);
+        assert_eq!(pinned(&table), [0, 1]);
+        table.pin_leading(&records);
+        assert!(pinned(&table).is_empty());
+
+        // Only some of the leading columns being pinned isn't enough.
+        table.toggle_pin(&records);
+        table.pin_leading(&records);
+        assert_eq!(pinned(&table), [0, 1]);
+    }
+
+    #[test]
+    fn moves_between_pinned_and_scrolling_columns() {
+        let records = records(&["a", "b", "c", "d"]);
+        let mut table = table(&records, 2);
+        table.toggle_pin(&records);
+
+        table.next_column();
+        assert_eq!(table.selected_col, Some(0));
+        table.previous_column();
+        assert_eq!(table.selected_col, Some(2));
+        table.previous_column();
+        assert_eq!(table.selected_col, Some(2));
+
+        table.selected_col = Some(3);
+        table.next_column();
+        assert_eq!(table.selected_col, Some(3));
+        table.previous_column();
+        assert_eq!(table.selected_col, Some(1));
+    }
+
+    #[test]
+    fn scrolls_past_the_pinned_columns() {
+        use Slot::*;
+
+        let records = records(&["a", "b", "c", "d", "e", "f"]);
+        let mut table = table(&records, 0);
+        table.toggle_pin(&records);
+
+        // Room for the pinned column and two more,
+        // or one more and the placeholders either side.
+        let width = HIGHLIGHT_WIDTH + 5 * 3 + PLACEHOLDER_WIDTH;
+        assert_eq!(
+            table.visible_columns(width),
+            [Column(0), Column(1), Column(2), MoreRight]
+        );
+
+        table.selected_col = Some(4);
+        assert_eq!(
+            table.visible_columns(width),
+            [Column(0), MoreLeft, Column(4), Column(5)]
+        );
+        assert_eq!(table.col_offset, 3);
+
+        table.previous_column();
+        assert_eq!(
+            table.visible_columns(width),
+            [Column(0), MoreLeft, Column(3), MoreRight]
+        );
+
+        // Selecting the pinned column leaves the rest where they are.
+        table.selected_col = Some(0);
+        assert_eq!(
+            table.visible_columns(width),
+            [Column(0), MoreLeft, Column(3), MoreRight]
+        );
+
+        // Pinning starts the rest from the first again.
+        table.selected_col = Some(3);
+        table.toggle_pin(&records);
+        assert_eq!(table.col_offset, 0);
+    }
+
+    #[test]
+    fn keeps_pins_by_name_when_the_headers_change() {
+        let before = records(&["a", "b", "c"]);
+        let mut table = table(&before, 1);
+        table.toggle_pin(&before);
+
+        let after = records(&["c", "a", "x", "b"]);
+        table.update_shape(&after);
+        table.reorder(&after);
+        assert_eq!(table.column_order(), [3, 0, 1, 2]);
+
+        let after = records(&["a", "c"]);
+        table.update_shape(&after);
+        table.reorder(&after);
+        assert!(pinned(&table).is_empty());
+    }
 }
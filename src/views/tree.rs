@@ -0,0 +1,447 @@
+use std::path::{Component, Path, PathBuf};
+
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, List, ListItem, ListState, Padding},
+};
+
+use super::FileView;
+
+struct Node {
+    name: String,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    kind: NodeKind,
+}
+
+enum NodeKind {
+    Dir { expanded: bool },
+    File(usize),
+}
+
+/// The files as a tree of the directories they're in,
+/// relative to the directory they have in common.
+pub struct FileTreeView {
+    root: PathBuf,
+
+    // The root directory is the first node.
+    nodes: Vec<Node>,
+
+    // Node the cursor is on when browsing.
+    cursor: usize,
+    list_state: ListState,
+}
+impl FileTreeView {
+    /// Build the tree from the paths of the files,
+    /// where files without a path are read from stdin.
+    pub fn new<'a>(paths: impl IntoIterator<Item = Option<&'a Path>> + Clone) -> Self {
+        let root = common_dir(paths.clone().into_iter().flatten());
+        let mut tree = Self {
+            root,
+            nodes: vec![Node {
+                name: String::new(),
+                parent: None,
+                children: vec![],
+                kind: NodeKind::Dir { expanded: true },
+            }],
+            cursor: 0,
+            list_state: ListState::default(),
+        };
+        for (file_idx, path) in paths.into_iter().enumerate() {
+            tree.add(file_idx, path);
+        }
+        tree.cursor = tree.nodes[0].children.first().copied().unwrap_or_default();
+        tree
+    }
+
+    /// Add a file under the directories in its path.
+    pub fn add(&mut self, file_idx: usize, path: Option<&Path>) {
+        let names: Vec<_> = match path {
+            Some(path) => path
+                .strip_prefix(&self.root)
+                .unwrap_or(path)
+                .components()
+                .filter_map(|component| match component {
+                    Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+                    _ => None,
+                })
+                .collect(),
+            None => vec!["stdin".into()],
+        };
+        let Some((name, dirs)) = names.split_last() else {
+            return;
+        };
+
+        let mut parent = 0;
+        for dir in dirs {
+            let existing = self.nodes[parent].children.iter().copied().find(|&child| {
+                let node = &self.nodes[child];
+                node.name == *dir && matches!(node.kind, NodeKind::Dir { .. })
+            });
+            parent = match existing {
+                Some(child) => child,
+                None => self.push(parent, dir.clone(), NodeKind::Dir { expanded: true }),
+            };
+        }
+        self.push(parent, name.clone(), NodeKind::File(file_idx));
+    }
+
+    fn push(&mut self, parent: usize, name: String, kind: NodeKind) -> usize {
+        let idx = self.nodes.len();
+        self.nodes.push(Node {
+            name,
+            parent: Some(parent),
+            children: vec![],
+            kind,
+        });
+        self.nodes[parent].children.push(idx);
+        idx
+    }
+
+    /// The nodes under `node`, in order, with their depth.
+    /// Those in collapsed directories are skipped if `visible_only`.
+    fn walk(&self, node: usize, depth: usize, visible_only: bool, out: &mut Vec<(usize, usize)>) {
+        for &child in &self.nodes[node].children {
+            out.push((child, depth));
+            match self.nodes[child].kind {
+                NodeKind::Dir { expanded } if expanded || !visible_only => {
+                    self.walk(child, depth + 1, visible_only, out)
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn rows(&self) -> Vec<(usize, usize)> {
+        let mut rows = vec![];
+        self.walk(0, 0, true, &mut rows);
+        rows
+    }
+
+    fn file_node(&self, file_idx: usize) -> Option<usize> {
+        self.nodes
+            .iter()
+            .position(|node| matches!(node.kind, NodeKind::File(idx) if idx == file_idx))
+    }
+
+    /// The files in the order they appear in the tree.
+    fn files(&self) -> Vec<usize> {
+        let mut nodes = vec![];
+        self.walk(0, 0, false, &mut nodes);
+        nodes
+            .into_iter()
+            .filter_map(|(node, _)| match self.nodes[node].kind {
+                NodeKind::File(file_idx) => Some(file_idx),
+                NodeKind::Dir { .. } => None,
+            })
+            .collect()
+    }
+
+    /// The file after `file_idx` in the tree, wrapping around.
+    pub fn next_file(&self, file_idx: usize) -> Option<usize> {
+        let files = self.files();
+        let pos = files.iter().position(|idx| *idx == file_idx)?;
+        files.get((pos + 1) % files.len()).copied()
+    }
+
+    /// The file before `file_idx` in the tree, wrapping around.
+    pub fn previous_file(&self, file_idx: usize) -> Option<usize> {
+        let files = self.files();
+        let pos = files.iter().position(|idx| *idx == file_idx)?;
+        files.get((pos + files.len() - 1) % files.len()).copied()
+    }
+
+    /// Expand the directories the file is in
+    /// and move the cursor to it.
+    pub fn reveal(&mut self, file_idx: usize) {
+        let Some(node) = self.file_node(file_idx) else {
+            return;
+        };
+        self.cursor = node;
+        let mut parent = self.nodes[node].parent;
+        while let Some(dir) = parent {
+            self.nodes[dir].kind = NodeKind::Dir { expanded: true };
+            parent = self.nodes[dir].parent;
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        let rows = self.rows();
+        if let Some(pos) = rows.iter().position(|(node, _)| *node == self.cursor) {
+            self.cursor = rows[(pos + 1) % rows.len()].0;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        let rows = self.rows();
+        if let Some(pos) = rows.iter().position(|(node, _)| *node == self.cursor) {
+            self.cursor = rows[(pos + rows.len() - 1) % rows.len()].0;
+        }
+    }
+
+    /// The file the cursor is on, if it isn't on a directory.
+    pub fn get_selected(&self) -> Option<usize> {
+        match self.nodes[self.cursor].kind {
+            NodeKind::File(file_idx) => Some(file_idx),
+            NodeKind::Dir { .. } => None,
+        }
+    }
+
+    /// Expand the directory the cursor is on.
+    pub fn expand(&mut self) {
+        if let NodeKind::Dir { expanded } = &mut self.nodes[self.cursor].kind {
+            *expanded = true;
+        }
+    }
+
+    /// Collapse the directory the cursor is on, or if it's
+    /// on a file or an already collapsed directory,
+    /// move up to the directory that's in.
+    pub fn collapse(&mut self) {
+        match &mut self.nodes[self.cursor].kind {
+            NodeKind::Dir { expanded } if *expanded => *expanded = false,
+            _ => {
+                if let Some(parent) = self.nodes[self.cursor].parent.filter(|idx| *idx != 0) {
+                    self.cursor = parent;
+                }
+            }
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        if let NodeKind::Dir { expanded } = &mut self.nodes[self.cursor].kind {
+            *expanded = !*expanded;
+        }
+    }
+
+    /// Render the tree, highlighting the selected file (or the
+    /// collapsed directory it's in) and, if browsing, the cursor.
+    pub fn render(
+        &mut self,
+        area: Rect,
+        buf: &mut Buffer,
+        files: &[FileView],
+        selected_file: usize,
+        browsing: bool,
+    ) {
+        let rows = self.rows();
+
+        // Files are indented to line up with
+        // the names of directories, if there are any.
+        let has_dirs = rows
+            .iter()
+            .any(|(node, _)| matches!(self.nodes[*node].kind, NodeKind::Dir { .. }));
+        let pad = if has_dirs { "  " } else { "" };
+
+        let mut selected = self.file_node(selected_file);
+        while let Some(node) = selected {
+            if rows.iter().any(|(row, _)| *row == node) {
+                break;
+            }
+            selected = self.nodes[node].parent;
+        }
+
+        let items: Vec<_> = rows
+            .iter()
+            .map(|&(node, depth)| {
+                let indent = "  ".repeat(depth);
+                let Node { name, kind, .. } = &self.nodes[node];
+                let item = match kind {
+                    NodeKind::Dir { expanded } => {
+                        let marker = if *expanded { "▾" } else { "▸" };
+                        ListItem::from(format!("{}{} {}/", indent, marker, name))
+                    }
+                    NodeKind::File(file_idx) if files[*file_idx].has_error() => {
+                        ListItem::from(format!("{}✗ {}", indent, name)).style(Color::Red)
+                    }
+                    NodeKind::File(_) => ListItem::from(format!("{}{}{}", indent, pad, name)),
+                };
+                if Some(node) == selected {
+                    item.style(Color::Green)
+                } else {
+                    item
+                }
+            })
+            .collect();
+
+        let (highlight_style, hint) = if browsing {
+            (
+                Style::new().bg(Color::Rgb(48, 48, 48)),
+                "h/l:fold enter:open",
+            )
+        } else {
+            (Style::new(), "tab:browse")
+        };
+        let block = Block::new()
+            .padding(Padding::horizontal(1))
+            .border_style(Color::Red)
+            .borders(Borders::BOTTOM)
+            .border_set(symbols::border::PLAIN)
+            .title_bottom(Line::from(hint).style(Color::DarkGray).right_aligned());
+
+        let highlighted = if browsing {
+            Some(self.cursor)
+        } else {
+            selected
+        };
+        self.list_state
+            .select(rows.iter().position(|(node, _)| Some(*node) == highlighted));
+
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(highlight_style);
+        StatefulWidget::render(list, area, buf, &mut self.list_state);
+    }
+}
+
+/// The deepest directory that all the paths are in.
+fn common_dir<'a>(paths: impl Iterator<Item = &'a Path>) -> PathBuf {
+    let mut common: Option<Vec<Component>> = None;
+    for path in paths {
+        let dir = path.parent().unwrap_or(Path::new(""));
+        common = Some(match common {
+            None => dir.components().collect(),
+            Some(common) => common
+                .into_iter()
+                .zip(dir.components())
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect(),
+        });
+    }
+    common.unwrap_or_default().iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(paths: &[&str]) -> FileTreeView {
+        FileTreeView::new(paths.iter().map(|path| Some(Path::new(path))))
+    }
+
+    /// The rows shown, indented by depth, with directories
+    /// ending in a slash.
+    fn listing(tree: &FileTreeView) -> Vec<String> {
+        tree.rows()
+            .into_iter()
+            .map(|(node, depth)| {
+                let node = &tree.nodes[node];
+                let slash = match node.kind {
+                    NodeKind::Dir { .. } => "/",
+                    NodeKind::File(_) => "",
+                };
+                format!("{}{}{}", "  ".repeat(depth), node.name, slash)
+            })
+            .collect()
+    }
+
+    fn common(paths: &[&str]) -> PathBuf {
+        common_dir(paths.iter().map(Path::new))
+    }
+
+    #[test]
+    fn finds_the_common_directory() {
+        assert_eq!(common(&["runs/a/x.csv", "runs/b/y.csv"]), Path::new("runs"));
+        assert_eq!(
+            common(&["runs/a/x.csv", "runs/a/y.csv"]),
+            Path::new("runs/a")
+        );
+        assert_eq!(common(&["runs/a/x.csv", "other/y.csv"]), Path::new(""));
+        assert_eq!(common(&["../a/x.csv", "../b/y.csv"]), Path::new(".."));
+        assert_eq!(common(&["x.csv"]), Path::new(""));
+        assert_eq!(common(&[]), Path::new(""));
+    }
+
+    #[test]
+    fn groups_files_by_directory() {
+        let tree = build(&[
+            "runs/a/metrics.csv",
+            "runs/b/metrics.csv",
+            "runs/a/log.csv",
+            "runs/top.csv",
+        ]);
+        assert_eq!(
+            listing(&tree),
+            [
+                "a/",
+                "  metrics.csv",
+                "  log.csv",
+                "b/",
+                "  metrics.csv",
+                "top.csv"
+            ]
+        );
+    }
+
+    #[test]
+    fn drops_parent_directories_from_relative_paths() {
+        let tree = build(&["../data/x.csv", "../data/sub/y.csv"]);
+        assert_eq!(tree.root, Path::new("../data"));
+        assert_eq!(listing(&tree), ["x.csv", "sub/", "  y.csv"]);
+
+        let tree = build(&["../x.csv", "y.csv"]);
+        assert_eq!(listing(&tree), ["x.csv", "y.csv"]);
+    }
+
+    #[test]
+    fn adds_files_outside_the_common_directory_by_their_whole_path() {
+        let mut tree = build(&["runs/a/x.csv", "runs/b/y.csv"]);
+        tree.add(2, Some(Path::new("runs/b/z.csv")));
+        tree.add(3, Some(Path::new("other/w.csv")));
+        tree.add(4, None);
+        assert_eq!(
+            listing(&tree),
+            ["a/", "  x.csv", "b/", "  y.csv", "  z.csv", "other/", "  w.csv", "stdin"]
+        );
+    }
+
+    #[test]
+    fn moves_through_files_in_tree_order() {
+        let tree = build(&["d/b/x.csv", "d/a.csv", "d/b/y.csv"]);
+        assert_eq!(tree.files(), [0, 2, 1]);
+
+        assert_eq!(tree.next_file(0), Some(2));
+        assert_eq!(tree.next_file(2), Some(1));
+        assert_eq!(tree.next_file(1), Some(0));
+        assert_eq!(tree.previous_file(0), Some(1));
+        assert_eq!(tree.previous_file(2), Some(0));
+        assert_eq!(tree.next_file(3), None);
+    }
+
+    #[test]
+    fn collapses_directories_and_moves_up_to_them() {
+        let mut tree = build(&["d/a/x.csv", "d/a/y.csv", "d/b.csv"]);
+        assert_eq!(tree.get_selected(), None);
+
+        // On a file, collapsing moves up to its directory,
+        // and then collapses that.
+        tree.select_next();
+        assert_eq!(tree.get_selected(), Some(0));
+        tree.collapse();
+        assert_eq!(tree.nodes[tree.cursor].name, "a");
+        tree.collapse();
+        assert_eq!(listing(&tree), ["a/", "b.csv"]);
+
+        // Already collapsed, or at the top, it stays put.
+        tree.collapse();
+        assert_eq!(tree.nodes[tree.cursor].name, "a");
+
+        // Files in collapsed directories are still moved through.
+        assert_eq!(tree.next_file(2), Some(0));
+
+        tree.reveal(1);
+        assert_eq!(listing(&tree).len(), 4);
+        assert_eq!(tree.get_selected(), Some(1));
+    }
+
+    #[test]
+    fn wraps_the_cursor_around() {
+        let mut tree = build(&["d/x.csv", "d/y.csv"]);
+        assert_eq!(tree.get_selected(), Some(0));
+        tree.select_previous();
+        assert_eq!(tree.get_selected(), Some(1));
+        tree.select_next();
+        assert_eq!(tree.get_selected(), Some(0));
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    time::{Duration, Instant},
+};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+
+/// How long a file has to go without changing before it's
+/// reloaded, so that it isn't read while half written.
+const SETTLE_TIME: Duration = Duration::from_millis(250);
+
+/// Watches directories for files being created or changed.
+/// Directories are watched rather than the files themselves
+/// so that files replaced by renaming over them are noticed.
+pub struct Watcher {
+    _watcher: RecommendedWatcher,
+    rx: Receiver<notify::Result<notify::Event>>,
+
+    // Changed files, with when they last changed.
+    pending: HashMap<PathBuf, Instant>,
+}
+impl Watcher {
+    pub fn new<'a>(
+        dirs: impl IntoIterator<Item = (&'a Path, RecursiveMode)>,
+    ) -> notify::Result<Self> {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+        for (dir, mode) in dirs {
+            watcher.watch(dir, mode)?;
+        }
+        Ok(Self {
+            _watcher: watcher,
+            rx,
+            pending: HashMap::new(),
+        })
+    }
+
+    /// The files that changed and have since settled.
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        self.changed_at(Instant::now())
+    }
+
+    /// The files that changed and have settled by `now`,
+    /// taking any changes since last time to be at `now`.
+    fn changed_at(&mut self, now: Instant) -> Vec<PathBuf> {
+        while let Ok(event) = self.rx.try_recv() {
+            let Ok(event) = event else {
+                continue;
+            };
+            if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                for path in event.paths {
+                    self.pending.insert(path, now);
+                }
+            }
+        }
+
+        let settled: Vec<_> = self
+            .pending
+            .iter()
+            .filter(|(_, changed)| now.duration_since(**changed) >= SETTLE_TIME)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in &settled {
+            self.pending.remove(path);
+        }
+        settled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::Sender;
+
+    use notify::{
+        event::{CreateKind, ModifyKind, RemoveKind},
+        Event,
+    };
+
+    use super::*;
+
+    /// A watcher that's sent events by hand rather than
+    /// watching anything.
+    fn watcher() -> (Watcher, Sender<notify::Result<Event>>) {
+        let (tx, rx) = mpsc::channel();
+        let watcher = Watcher {
+            _watcher: notify::recommended_watcher(|_| {}).unwrap(),
+            rx,
+            pending: HashMap::new(),
+        };
+        (watcher, tx)
+    }
+
+    fn event(kind: EventKind, path: &str) -> notify::Result<Event> {
+        Ok(Event::new(kind).add_path(PathBuf::from(path)))
+    }
+
+    #[test]
+    fn waits_for_files_to_settle() {
+        let (mut watcher, tx) = watcher();
+        let start = Instant::now();
+        let after = |millis| start + Duration::from_millis(millis);
+        let modify = EventKind::Modify(ModifyKind::Any);
+
+        tx.send(event(EventKind::Create(CreateKind::File), "a.csv"))
+            .unwrap();
+        tx.send(event(modify, "a.csv")).unwrap();
+        tx.send(event(modify, "b.csv")).unwrap();
+        tx.send(event(EventKind::Remove(RemoveKind::File), "c.csv"))
+            .unwrap();
+        assert!(watcher.changed_at(start).is_empty());
+
+        // Changing again puts off reloading the file.
+        tx.send(event(modify, "a.csv")).unwrap();
+        assert!(watcher.changed_at(after(200)).is_empty());
+        assert_eq!(watcher.changed_at(after(250)), [PathBuf::from("b.csv")]);
+        assert!(watcher.changed_at(after(400)).is_empty());
+
+        // Any number of changes are coalesced into one.
+        assert_eq!(watcher.changed_at(after(450)), [PathBuf::from("a.csv")]);
+        assert!(watcher.changed_at(after(1000)).is_empty());
+    }
+}